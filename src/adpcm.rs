@@ -0,0 +1,202 @@
+// block-based ADPCM as found in WAV files (mono); each block starts with a header that
+// resets the decoder, so a corrupt block only affects itself
+
+use crate::vox::ADPCM_INDEX_TABLE;
+
+// ---- IMA/DVI ADPCM ----
+// header: initial predictor (i16 LE), step index (u8), reserved (u8)
+pub const IMA_HEADER_LEN: usize = 4;
+
+struct ImaState {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ImaState {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step_size = IMA_STEP_TABLE[self.step_index as usize];
+
+        // same bitwise scaling as VOX: step/8 always, plus step, step/2, step/4 per magnitude bit
+        let mut delta = step_size >> 3;
+        if nibble & 0b0100 != 0 {
+            delta += step_size;
+        }
+        if nibble & 0b0010 != 0 {
+            delta += step_size >> 1;
+        }
+        if nibble & 0b0001 != 0 {
+            delta += step_size >> 2;
+        }
+        if nibble & 0b1000 != 0 {
+            delta = -delta;
+        }
+
+        // full 16-bit range, unlike VOX's 12
+        self.predictor = i32::clamp(self.predictor + delta, i16::MIN as i32, i16::MAX as i32);
+        self.step_index = i32::clamp(
+            self.step_index + ADPCM_INDEX_TABLE[nibble as usize] as i32,
+            0,
+            IMA_STEP_TABLE.len() as i32 - 1,
+        );
+
+        self.predictor as i16
+    }
+}
+
+pub fn ima_decode_block(block: &[u8]) -> Vec<i16> {
+    if block.len() < IMA_HEADER_LEN {
+        return Vec::new();
+    }
+
+    let mut state = ImaState {
+        predictor: i16::from_le_bytes([block[0], block[1]]) as i32,
+        // arbitrary data can point past the end of the table
+        step_index: i32::min(block[2] as i32, IMA_STEP_TABLE.len() as i32 - 1),
+    };
+
+    // header predictor is the block's first sample
+    let mut samples = vec![state.predictor as i16];
+    for byte in &block[IMA_HEADER_LEN..] {
+        // low nibble first
+        samples.push(state.decode(byte & 0b1111));
+        samples.push(state.decode(byte >> 4));
+    }
+
+    samples
+}
+
+// ---- MICROSOFT ADPCM ----
+// header: predictor (coefficient set) index (u8), initial delta (i16 LE),
+// then the two most recent samples (i16 LE each, newest first)
+pub const MS_HEADER_LEN: usize = 7;
+
+struct MsState {
+    coeff1: i32,
+    coeff2: i32,
+    delta: i32,
+    sample1: i32,
+    sample2: i32,
+}
+
+impl MsState {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        // nibble is a signed 4-bit value
+        let signed_nibble = if nibble & 0b1000 != 0 {
+            nibble as i32 - 16
+        } else {
+            nibble as i32
+        };
+
+        // second-order linear prediction from the last two samples; coefficients are 8.8 fixed point
+        let predicted = (self.sample1 * self.coeff1 + self.sample2 * self.coeff2) >> 8;
+        let sample = i32::clamp(
+            predicted + signed_nibble * self.delta,
+            i16::MIN as i32,
+            i16::MAX as i32,
+        );
+
+        self.sample2 = self.sample1;
+        self.sample1 = sample;
+
+        // adapt step size; keep it from collapsing to 0 or (with arbitrary data) overflowing
+        self.delta = i32::clamp(
+            (MS_ADAPTATION_TABLE[nibble as usize] * self.delta) >> 8,
+            16,
+            i32::MAX / 768,
+        );
+
+        sample as i16
+    }
+}
+
+pub fn ms_decode_block(block: &[u8]) -> Vec<i16> {
+    if block.len() < MS_HEADER_LEN {
+        return Vec::new();
+    }
+
+    // arbitrary data can point past the end of the coefficient table
+    let coeff_index = usize::min(block[0] as usize, MS_COEFF1_TABLE.len() - 1);
+    let mut state = MsState {
+        coeff1: MS_COEFF1_TABLE[coeff_index],
+        coeff2: MS_COEFF2_TABLE[coeff_index],
+        delta: i16::from_le_bytes([block[1], block[2]]) as i32,
+        sample1: i16::from_le_bytes([block[3], block[4]]) as i32,
+        sample2: i16::from_le_bytes([block[5], block[6]]) as i32,
+    };
+
+    // header samples come out oldest first
+    let mut samples = vec![state.sample2 as i16, state.sample1 as i16];
+    for byte in &block[MS_HEADER_LEN..] {
+        // high nibble first
+        samples.push(state.decode(byte >> 4));
+        samples.push(state.decode(byte & 0b1111));
+    }
+
+    samples
+}
+
+// ---- TABLES ----
+// IMA step sizes: roughly 10% apart, 7 to 32767
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+// MS ADPCM's seven standard predictor coefficient pairs
+const MS_COEFF1_TABLE: [i32; 7] = [256, 512, 0, 192, 240, 460, 392];
+const MS_COEFF2_TABLE: [i32; 7] = [0, -256, 0, 64, 0, -208, -232];
+
+// step size multipliers (8.8 fixed point), indexed by the whole nibble
+const MS_ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ima_header_predictor_is_the_first_sample() {
+        assert_eq!(ima_decode_block(&[0x34, 0x12, 0, 0]), vec![0x1234]);
+        assert_eq!(ima_decode_block(&[0x00, 0x80, 0, 0]), vec![i16::MIN]);
+    }
+
+    #[test]
+    fn ima_decodes_the_low_nibble_first() {
+        // step 7: nibble 0 adds 7 >> 3 = 0, nibble 7 adds 0 + 7 + 3 + 1
+        assert_eq!(ima_decode_block(&[0, 0, 0, 0, 0x70]), vec![0, 0, 11]);
+    }
+
+    #[test]
+    fn ima_step_index_is_clamped_to_the_table() {
+        // 200 reads as 88 (step 32767): nibble 1 adds 4095 + 8191, then index 87
+        // (step 29794) and nibble 0 adds 29794 >> 3
+        let expected = vec![0, 12286, 16010];
+        assert_eq!(ima_decode_block(&[0, 0, 200, 0, 0x01]), expected);
+        assert_eq!(ima_decode_block(&[0, 0, 88, 0, 0x01]), expected);
+    }
+
+    #[test]
+    fn ms_header_samples_come_out_oldest_first() {
+        // coefficient set 0, delta 16, sample1 (newest) 100, sample2 (oldest) 200
+        let block = [0, 16, 0, 100, 0, 200, 0];
+        assert_eq!(ms_decode_block(&block), vec![200, 100]);
+    }
+
+    #[test]
+    fn ms_delta_is_floored_at_16() {
+        // set 0 predicts sample1; nibble 0 would take delta to 230 * 16 >> 8 = 14,
+        // so the following nibble 1 adds 16, not 14
+        let block = [0, 16, 0, 100, 0, 200, 0, 0x01];
+        assert_eq!(ms_decode_block(&block), vec![200, 100, 100, 116]);
+    }
+
+    #[test]
+    fn short_blocks_decode_to_nothing() {
+        assert!(ima_decode_block(&[0; IMA_HEADER_LEN - 1]).is_empty());
+        assert!(ms_decode_block(&[0; MS_HEADER_LEN - 1]).is_empty());
+    }
+}
@@ -2,7 +2,7 @@ use std::f64::consts::PI;
 
 // use crate::cli::Args;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum FilterAlgorithm {
     Lpf1P,
     Lpf1,
@@ -11,9 +11,12 @@ pub enum FilterAlgorithm {
     Hpf2,
     Bpf2,
     Bsf2,
+    LowShelf,
+    HighShelf,
+    Peak,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct AudioFilterParameters {
     algorithm: FilterAlgorithm,
     fc: f64,
@@ -59,6 +62,11 @@ impl Biquad {
     }
     pub fn reset() {}
 
+    // for designs computed outside AudioFilter, e.g. the fixed K-weighting stages
+    pub fn set_coeffs(&mut self, a0: f64, a1: f64, a2: f64, b1: f64, b2: f64) {
+        self.coeff_array[..5].copy_from_slice(&[a0, a1, a2, b1, b2]);
+    }
+
     pub fn process_sample(&mut self, xn: f64) -> f64 {
         // canonical form only
         // mix direct/filtered
@@ -144,25 +152,320 @@ impl AudioFilter {
         self.biquad.coeff_array[6] = 0.0; // d0
 
         let filter_algorithm = self.parameters.algorithm;
-        let fc = self.parameters.fc;
+        // keep fc just under Nyquist so the tan()/sin() terms stay finite
+        let fc = f64::min(self.parameters.fc, 0.49 * self.sample_rate as f64);
         let q = self.parameters.q;
+        // linear gain for the EQ designs, which mix the filtered signal back with dry
+        let mu = f64::powf(10.0, self.parameters.boost_cut_db / 20.0);
+
+        // shared by all designs below; tan(theta_c / 2) blows up at Nyquist
+        let theta_c = (2.0 * PI * fc) / self.sample_rate as f64;
+
+        match filter_algorithm {
+            FilterAlgorithm::Lpf1P => {
+                // one-pole: no zeros, only the b1 feedback term
+                let gamma = 2.0 - f64::cos(theta_c);
+
+                let b1 = f64::sqrt(gamma * gamma - 1.0) - gamma;
+
+                self.biquad.coeff_array[0] = 1.0 + b1; // a0
+                self.biquad.coeff_array[3] = b1; // b1
+            }
+            FilterAlgorithm::Lpf1 => {
+                // first-order bilinear: zero at Nyquist
+                let gamma = f64::cos(theta_c) / (1.0 + f64::sin(theta_c));
+
+                self.biquad.coeff_array[0] = (1.0 - gamma) / 2.0; // a0
+                self.biquad.coeff_array[1] = (1.0 - gamma) / 2.0; // a1
+                self.biquad.coeff_array[3] = -gamma; // b1
+            }
+            FilterAlgorithm::Hpf1 => {
+                // first-order bilinear: zero at DC
+                let gamma = f64::cos(theta_c) / (1.0 + f64::sin(theta_c));
+
+                self.biquad.coeff_array[0] = (1.0 + gamma) / 2.0; // a0
+                self.biquad.coeff_array[1] = -(1.0 + gamma) / 2.0; // a1
+                self.biquad.coeff_array[3] = -gamma; // b1
+            }
+            FilterAlgorithm::Lpf2 | FilterAlgorithm::Hpf2 => {
+                let d = 1.0 / q;
+
+                let beta_numerator = 1.0 - (d / 2.0) * f64::sin(theta_c);
+                let beta_denominator = 1.0 + (d / 2.0) * f64::sin(theta_c);
+                let beta = 0.5 * (beta_numerator / beta_denominator);
+
+                let gamma = (0.5 + beta) * f64::cos(theta_c);
+
+                // only the sign of gamma in alpha and of a1 differ between LPF/HPF
+                let (alpha, a1_sign) = if filter_algorithm == FilterAlgorithm::Lpf2 {
+                    ((0.5 + beta - gamma) / 2.0, 1.0)
+                } else {
+                    ((0.5 + beta + gamma) / 2.0, -1.0)
+                };
+
+                self.biquad.coeff_array[0] = alpha; // a0
+                self.biquad.coeff_array[1] = a1_sign * alpha * 2.0; // a1
+                self.biquad.coeff_array[2] = alpha; // a2
+                self.biquad.coeff_array[3] = -2.0 * gamma; // b1
+                self.biquad.coeff_array[4] = 2.0 * beta; // b2
+            }
+            FilterAlgorithm::Bpf2 | FilterAlgorithm::Bsf2 => {
+                // pre-warped analog prototype; Q sets bandwidth around fc
+                let k = f64::tan(theta_c / 2.0);
+                let delta = k * k * q + k + q;
+
+                if filter_algorithm == FilterAlgorithm::Bpf2 {
+                    self.biquad.coeff_array[0] = k / delta; // a0
+                    self.biquad.coeff_array[1] = 0.0; // a1
+                    self.biquad.coeff_array[2] = -k / delta; // a2
+                } else {
+                    self.biquad.coeff_array[0] = q * (k * k + 1.0) / delta; // a0
+                    self.biquad.coeff_array[1] = 2.0 * q * (k * k - 1.0) / delta; // a1
+                    self.biquad.coeff_array[2] = q * (k * k + 1.0) / delta; // a2
+                }
+                self.biquad.coeff_array[3] = 2.0 * q * (k * k - 1.0) / delta; // b1
+                self.biquad.coeff_array[4] = (k * k * q - k + q) / delta; // b2
+            }
+            FilterAlgorithm::LowShelf | FilterAlgorithm::HighShelf => {
+                // first-order shelf: dry + (mu - 1) * LPF/HPF; Q is unused
+                let beta = if filter_algorithm == FilterAlgorithm::LowShelf {
+                    4.0 / (1.0 + mu)
+                } else {
+                    (1.0 + mu) / 4.0
+                };
+                let delta = beta * f64::tan(theta_c / 2.0);
+                let gamma = (1.0 - delta) / (1.0 + delta);
+
+                if filter_algorithm == FilterAlgorithm::LowShelf {
+                    self.biquad.coeff_array[0] = (1.0 - gamma) / 2.0; // a0
+                    self.biquad.coeff_array[1] = (1.0 - gamma) / 2.0; // a1
+                } else {
+                    self.biquad.coeff_array[0] = (1.0 + gamma) / 2.0; // a0
+                    self.biquad.coeff_array[1] = -(1.0 + gamma) / 2.0; // a1
+                }
+                self.biquad.coeff_array[3] = -gamma; // b1
+
+                self.biquad.coeff_array[5] = mu - 1.0; // c0
+                self.biquad.coeff_array[6] = 1.0; // d0
+            }
+            FilterAlgorithm::Peak => {
+                // bell: dry + (mu - 1) * BPF; bandwidth narrows with boost/cut
+                let zeta = 4.0 / (1.0 + mu);
+                // a wide bell near Nyquist would put tan() past its pole and flip the
+                // sign of beta, making the filter unstable; keep the argument under pi/2
+                let half_bandwidth = f64::min(theta_c / (2.0 * q), 0.49 * PI);
+                let tan_term = zeta * f64::tan(half_bandwidth);
+
+                let beta = 0.5 * ((1.0 - tan_term) / (1.0 + tan_term));
+                let gamma = (0.5 + beta) * f64::cos(theta_c);
+
+                self.biquad.coeff_array[0] = 0.5 - beta; // a0
+                self.biquad.coeff_array[1] = 0.0; // a1
+                self.biquad.coeff_array[2] = -(0.5 - beta); // a2
+                self.biquad.coeff_array[3] = -2.0 * gamma; // b1
+                self.biquad.coeff_array[4] = 2.0 * beta; // b2
+
+                self.biquad.coeff_array[5] = mu - 1.0; // c0
+                self.biquad.coeff_array[6] = 1.0; // d0
+            }
+        }
+    }
+}
+
+// ordered cascade of filters, e.g. from repeated --filter options
+pub struct FilterChain {
+    filters: Vec<AudioFilter>,
+}
+
+impl FilterChain {
+    pub fn new(params: &[AudioFilterParameters], sample_rate: u32) -> FilterChain {
+        let filters = params
+            .iter()
+            .map(|p| {
+                let mut filter = AudioFilter::new(p, sample_rate);
+                filter.calculate_filter_coeffs();
+                filter
+            })
+            .collect();
+
+        FilterChain { filters }
+    }
+
+    pub fn process_sample(&mut self, xn: f64) -> f64 {
+        // output of each stage feeds the next, in command-line order
+        self.filters
+            .iter_mut()
+            .fold(xn, |sample, filter| filter.process_sample(sample))
+    }
+}
 
-        if filter_algorithm == FilterAlgorithm::Hpf2 {
-            let theta_c = (2.0 * PI * fc) / self.sample_rate as f64;
-            let d = 1.0 / q;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let beta_numerator = 1.0 - (d / 2.0) * f64::sin(theta_c);
-            let beta_denominator = 1.0 + (d / 2.0) * f64::sin(theta_c);
-            let beta = 0.5 * (beta_numerator / beta_denominator);
+    const FS: u32 = 48000;
+    const FC: f64 = 1000.0;
+
+    // steady-state gain in dB of the chain for a sine at freq; 1 s of signal, measured
+    // over the second half (a whole number of cycles for the frequencies used here)
+    fn gain_db(params: &[AudioFilterParameters], freq: f64) -> f64 {
+        let mut chain = FilterChain::new(params, FS);
+        let output: Vec<f64> = (0..FS)
+            .map(|n| chain.process_sample(f64::sin(2.0 * PI * freq * n as f64 / FS as f64)))
+            .collect();
+
+        let settled = &output[FS as usize / 2..];
+        let rms = f64::sqrt(settled.iter().map(|y| y * y).sum::<f64>() / settled.len() as f64);
+        20.0 * f64::log10(rms * f64::sqrt(2.0))
+    }
+
+    fn filter(algorithm: FilterAlgorithm, q: f64, boost_cut_db: f64) -> AudioFilterParameters {
+        AudioFilterParameters::new(algorithm, FC, q, boost_cut_db)
+    }
+
+    fn assert_db(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {expected} dB (± {tolerance}), got {actual} dB"
+        );
+    }
+
+    #[test]
+    fn first_order_filters_are_3db_down_at_cutoff() {
+        for algorithm in [
+            FilterAlgorithm::Lpf1P,
+            FilterAlgorithm::Lpf1,
+            FilterAlgorithm::Hpf1,
+        ] {
+            assert_db(gain_db(&[filter(algorithm, 0.707, 0.0)], FC), -3.01, 0.05);
+        }
+    }
+
+    #[test]
+    fn first_order_filters_roll_off_a_decade_away() {
+        let lpf1 = [filter(FilterAlgorithm::Lpf1, 0.707, 0.0)];
+        assert_db(gain_db(&lpf1, FC / 10.0), 0.0, 0.1);
+        assert!(gain_db(&lpf1, FC * 10.0) < -20.0);
+
+        let hpf1 = [filter(FilterAlgorithm::Hpf1, 0.707, 0.0)];
+        assert_db(gain_db(&hpf1, FC * 10.0), 0.0, 0.1);
+        assert_db(gain_db(&hpf1, FC / 10.0), -20.0, 0.5);
+
+        // the one-pole has no zero at Nyquist, so it only approaches -20 dB/decade
+        let lpf1p = [filter(FilterAlgorithm::Lpf1P, 0.707, 0.0)];
+        assert_db(gain_db(&lpf1p, FC / 10.0), 0.0, 0.1);
+        assert!(gain_db(&lpf1p, FC * 10.0) < -15.0);
+    }
+
+    #[test]
+    fn butterworth_second_order_filters_are_3db_down_at_cutoff() {
+        let lpf2 = [filter(FilterAlgorithm::Lpf2, 0.707, 0.0)];
+        assert_db(gain_db(&lpf2, FC), -3.01, 0.05);
+        assert_db(gain_db(&lpf2, FC / 10.0), 0.0, 0.1);
+        assert!(gain_db(&lpf2, FC * 10.0) < -40.0);
+
+        let hpf2 = [filter(FilterAlgorithm::Hpf2, 0.707, 0.0)];
+        assert_db(gain_db(&hpf2, FC), -3.01, 0.05);
+        assert_db(gain_db(&hpf2, FC * 10.0), 0.0, 0.1);
+        assert_db(gain_db(&hpf2, FC / 10.0), -40.0, 0.5);
+    }
+
+    #[test]
+    fn band_pass_peaks_and_band_stop_nulls_at_centre() {
+        let bpf2 = [filter(FilterAlgorithm::Bpf2, 2.0, 0.0)];
+        assert_db(gain_db(&bpf2, FC), 0.0, 0.05);
+        assert!(gain_db(&bpf2, FC / 10.0) < -20.0);
+        assert!(gain_db(&bpf2, FC * 10.0) < -20.0);
+
+        let bsf2 = [filter(FilterAlgorithm::Bsf2, 2.0, 0.0)];
+        assert!(gain_db(&bsf2, FC) < -60.0);
+        assert_db(gain_db(&bsf2, FC / 10.0), 0.0, 0.1);
+        assert_db(gain_db(&bsf2, FC * 10.0), 0.0, 0.1);
+    }
+
+    #[test]
+    fn shelves_and_peak_boost_and_cut() {
+        // first-order shelves are still ~0.2 dB off flat a decade from fc
+        let low_shelf = [filter(FilterAlgorithm::LowShelf, 0.707, 6.0)];
+        assert_db(gain_db(&low_shelf, FC / 10.0), 6.0, 0.1);
+        assert_db(gain_db(&low_shelf, FC * 10.0), 0.0, 0.25);
+
+        let high_shelf = [filter(FilterAlgorithm::HighShelf, 0.707, -6.0)];
+        assert_db(gain_db(&high_shelf, FC * 10.0), -6.0, 0.1);
+        assert_db(gain_db(&high_shelf, FC / 10.0), 0.0, 0.25);
+
+        for boost_cut_db in [6.0, -6.0] {
+            let peak = [filter(FilterAlgorithm::Peak, 2.0, boost_cut_db)];
+            assert_db(gain_db(&peak, FC), boost_cut_db, 0.05);
+            assert_db(gain_db(&peak, FC / 10.0), 0.0, 0.1);
+            assert_db(gain_db(&peak, FC * 10.0), 0.0, 0.1);
+        }
+    }
+
+    #[test]
+    fn chain_runs_filters_in_the_order_given() {
+        let params = [
+            filter(FilterAlgorithm::Hpf2, 0.707, 0.0),
+            filter(FilterAlgorithm::Peak, 2.0, 6.0),
+            filter(FilterAlgorithm::Lpf1, 0.707, 0.0),
+        ];
+        let mut chain = FilterChain::new(&params, FS);
+        let mut stages: Vec<AudioFilter> = params
+            .iter()
+            .map(|p| {
+                let mut filter = AudioFilter::new(p, FS);
+                filter.calculate_filter_coeffs();
+                filter
+            })
+            .collect();
+
+        // floating point makes the cascade order visible, so match it exactly
+        for n in 0..1000 {
+            let x = f64::sin(n as f64 * 0.37) + 0.25 * f64::cos(n as f64 * 2.1);
+            let expected = stages
+                .iter_mut()
+                .fold(x, |sample, stage| stage.process_sample(sample));
+            assert_eq!(chain.process_sample(x), expected);
+        }
+
+        // and the gains of the stages add up
+        assert_db(gain_db(&params, FC), -3.01 + 6.0 - 3.01, 0.05);
+    }
 
-            let gamma = (0.5 + beta) * f64::cos(theta_c);
-            let alpha = (0.5 + beta + gamma) / 2.0;
+    #[test]
+    fn cutoff_above_nyquist_gives_finite_coefficients() {
+        for algorithm in [
+            FilterAlgorithm::Lpf1P,
+            FilterAlgorithm::Lpf1,
+            FilterAlgorithm::Hpf1,
+            FilterAlgorithm::Lpf2,
+            FilterAlgorithm::Hpf2,
+            FilterAlgorithm::Bpf2,
+            FilterAlgorithm::Bsf2,
+            FilterAlgorithm::LowShelf,
+            FilterAlgorithm::HighShelf,
+            FilterAlgorithm::Peak,
+        ] {
+            for fc in [0.5 * FS as f64, FS as f64, 1e6] {
+                let params = AudioFilterParameters::new(algorithm, fc, 0.707, 6.0);
+                let mut filter = AudioFilter::new(&params, FS);
+                filter.calculate_filter_coeffs();
 
-            self.biquad.coeff_array[0] = alpha; // a0
-            self.biquad.coeff_array[1] = -alpha * 2.0; // a1
-            self.biquad.coeff_array[2] = alpha; // a2
-            self.biquad.coeff_array[3] = -2.0 * gamma; // b1
-            self.biquad.coeff_array[4] = 2.0 * beta; // b2
+                assert!(
+                    filter.biquad.coeff_array.iter().all(|c| c.is_finite()),
+                    "{algorithm:?} at {fc} Hz: {:?}",
+                    filter.biquad.coeff_array
+                );
+                let output: Vec<f64> = (0..1000)
+                    .map(|n| filter.process_sample(if n % 2 == 0 { 1.0 } else { -1.0 }))
+                    .collect();
+                assert!(
+                    output.iter().all(|y| y.is_finite()),
+                    "{algorithm:?} at {fc} Hz: {:?} {:?}",
+                    filter.biquad.coeff_array,
+                    &output[990..]
+                );
+            }
         }
     }
 }
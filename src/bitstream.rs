@@ -0,0 +1,90 @@
+use crate::cli::BitOrder;
+
+// ---- PACKED BITSTREAM ----
+// splits data into consecutive `width`-bit values (1-32), ignoring byte boundaries;
+// leftover bits at the end that don't fill a whole value are dropped
+pub fn unpack(data: &[u8], width: u32, order: BitOrder) -> Vec<u32> {
+    let mask: u64 = (1 << width) - 1;
+    let mut values: Vec<u32> = Vec::with_capacity(data.len() * 8 / width as usize);
+
+    // bits read but not yet used; never more than width + 7, so a u64 is plenty
+    let mut accumulator: u64 = 0;
+    let mut num_bits: u32 = 0;
+
+    for byte in data {
+        match order {
+            // first bit is each byte's MSB, and becomes the value's MSB
+            BitOrder::MsbFirst => {
+                accumulator = (accumulator << 8) | *byte as u64;
+                num_bits += 8;
+
+                while num_bits >= width {
+                    num_bits -= width;
+                    values.push(((accumulator >> num_bits) & mask) as u32);
+                }
+                // drop bits already used, so the shift above never overflows
+                accumulator &= (1 << num_bits) - 1;
+            }
+            // first bit is each byte's LSB, and becomes the value's LSB
+            BitOrder::LsbFirst => {
+                accumulator |= (*byte as u64) << num_bits;
+                num_bits += 8;
+
+                while num_bits >= width {
+                    values.push((accumulator & mask) as u32);
+                    accumulator >>= width;
+                    num_bits -= width;
+                }
+            }
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twelve_bit_values_in_either_order() {
+        let data = [0xab, 0xcd, 0xef];
+        assert_eq!(unpack(&data, 12, BitOrder::MsbFirst), [0xabc, 0xdef]);
+        assert_eq!(unpack(&data, 12, BitOrder::LsbFirst), [0xdab, 0xefc]);
+    }
+
+    #[test]
+    fn single_bits_in_either_order() {
+        assert_eq!(
+            unpack(&[0b1100_1010], 1, BitOrder::MsbFirst),
+            [1, 1, 0, 0, 1, 0, 1, 0]
+        );
+        assert_eq!(
+            unpack(&[0b1100_1010], 1, BitOrder::LsbFirst),
+            [0, 1, 0, 1, 0, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn full_32_bit_values() {
+        let data = [0x12, 0x34, 0x56, 0x78, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(
+            unpack(&data, 32, BitOrder::MsbFirst),
+            [0x12345678, 0xffffffff]
+        );
+        assert_eq!(
+            unpack(&data, 32, BitOrder::LsbFirst),
+            [0x78563412, 0xffffffff]
+        );
+    }
+
+    #[test]
+    fn leftover_bits_are_dropped() {
+        // 24 bits: two 10-bit values, 4 bits left over
+        let data = [0xab, 0xcd, 0xef];
+        assert_eq!(unpack(&data, 10, BitOrder::MsbFirst), [0x2af, 0x0de]);
+        assert_eq!(unpack(&data, 10, BitOrder::LsbFirst), [0x1ab, 0x3f3]);
+        assert!(unpack(&data[..1], 12, BitOrder::MsbFirst).is_empty());
+        assert!(unpack(&[], 8, BitOrder::LsbFirst).is_empty());
+    }
+}
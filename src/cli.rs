@@ -1,6 +1,7 @@
 use clap::{Parser, ValueEnum};
-// use std::collections::HashMap;
-// use std::sync::LazyLock;
+
+use crate::biquad::{AudioFilterParameters, FilterAlgorithm};
+use crate::template;
 
 // ---- CLI PARSER ----
 #[derive(Parser, Debug)]
@@ -14,33 +15,363 @@ pub struct Args {
     #[arg(short = 'a', long, default_value_t = String::from(""))]
     pub append: String,
 
+    // write every output straight into --output instead of mirroring the input's subfolders
+    #[arg(long, default_value_t = false)]
+    pub flat: bool,
+
+    // name outputs from placeholders instead, e.g. "{relpath}_{format}_{rate}"; ".wav" is added
+    #[arg(long, value_parser = parse_name_template, conflicts_with_all = ["append", "flat"])]
+    pub name_template: Option<String>,
+
     #[arg(short = 'm', long, default_value_t = 0)]
     pub min: u64,
 
-    #[arg(short = 's', long, default_value_t = 44100)]
-    pub samplerate: u32,
+    #[arg(long)]
+    pub max: Option<u64>,
 
-    #[clap(short = 'f', long, value_enum, default_value_t=SampleFormat::Int16)]
-    pub format: SampleFormat,
+    // glob patterns, matched against each path below --input; repeatable
+    #[arg(long)]
+    pub include: Vec<String>,
+
+    // also skips everything inside matching folders
+    #[arg(long)]
+    pub exclude: Vec<String>,
+
+    // comma-separated extensions, without the dot; not case-sensitive
+    #[arg(long, value_delimiter = ',')]
+    pub include_ext: Vec<String>,
+
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_ext: Vec<String>,
+
+    // 1 = only files directly inside --input
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+
+    #[arg(long, default_value_t = false)]
+    pub follow_links: bool,
+
+    // skip files and folders whose names start with "."
+    #[arg(long, default_value_t = false)]
+    pub skip_hidden: bool,
+
+    // region of each file to read; bytes, hex (0x...) or a percentage of the file
+    #[arg(long, value_parser = parse_position)]
+    pub offset: Option<Position>,
+
+    #[arg(long, value_parser = parse_position)]
+    pub length: Option<Position>,
+
+    // repeatable; each range is rendered to its own file
+    #[arg(long = "range", value_parser = parse_range, conflicts_with_all = ["offset", "length"])]
+    pub ranges: Vec<ByteRange>,
+
+    // comma-separated lists render every combination, e.g. -f int8,int16 -e little,big
+    #[arg(
+        short = 's',
+        long = "samplerate",
+        value_name = "SAMPLERATE",
+        value_delimiter = ',',
+        default_value = "44100"
+    )]
+    pub samplerates: Vec<u32>,
+
+    #[clap(
+        short = 'f',
+        long = "format",
+        value_name = "FORMAT",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "int16"
+    )]
+    pub formats: Vec<SampleFormat>,
+
+    #[clap(
+        short = 'e',
+        long = "endian",
+        value_name = "ENDIAN",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "little"
+    )]
+    pub endians: Vec<Endianness>,
+
+    // sample width for packed-int/packed-uint
+    #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..=32))]
+    pub bits: u32,
+
+    #[clap(long, value_enum, default_value_t=BitOrder::MsbFirst)]
+    pub bit_order: BitOrder,
+
+    // pdm input bit rate as a multiple of --samplerate
+    #[arg(long, default_value_t = 64, value_parser = parse_decimation)]
+    pub decimation: u32,
+
+    // unset: each nibble-stream ADPCM format's usual order
+    #[clap(long, value_enum)]
+    pub nibble_order: Option<NibbleOrder>,
+
+    // bytes per block for ima-adpcm/ms-adpcm, incl. header; WAV files call this "block align"
+    #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u32).range(8..))]
+    pub block_size: u32,
+
+    #[clap(long, value_enum, default_value_t=Sanitize::Clamp)]
+    pub sanitize: Sanitize,
+
+    #[clap(short = 'F', long, value_enum)]
+    pub out_format: Option<OutFormat>,
+
+    // decoded samples are read as interleaved frames of this many channels
+    #[arg(short = 'c', long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub channels: u16,
+
+    #[clap(long, value_enum, default_value_t=Layout::Interleaved)]
+    pub layout: Layout,
+
+    // planar channels start at these byte offsets instead; sets the number of channels
+    #[arg(long, value_delimiter = ',', value_parser = parse_position)]
+    pub plane_offsets: Vec<Position>,
+
+    #[clap(short = 'd', long, value_enum, default_value_t=Dither::None)]
+    pub dither: Dither,
+
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
 
-    #[clap(short = 'e', long, value_enum, default_value_t=Endianness::Little)]
-    pub endian: Endianness,
-    // #[clap(short = 'F', long, value_enum)]
-    // pub out_format: Option<SampleFormat>,
     #[arg(short = 'r', long, default_value_t = false)]
     pub raw: bool,
 
-    #[arg(short = 'g', long, default_value_t = -8.0)]
+    // reverse mode: encode .wav input to Dialogic .vox instead
+    #[arg(long, default_value_t = false, conflicts_with_all = ["offset", "length", "ranges"])]
+    pub to_vox: bool,
+
+    #[arg(short = 'g', long, default_value_t = -8.0, allow_negative_numbers = true)]
     pub gain: f64,
+
+    #[arg(short = 'n', long, allow_negative_numbers = true)]
+    pub normalize: Option<f64>,
+
+    #[arg(
+        short = 'l',
+        long,
+        allow_negative_numbers = true,
+        conflicts_with = "normalize"
+    )]
+    pub lufs: Option<f64>,
+
+    #[arg(long, allow_negative_numbers = true, requires = "lufs")]
+    pub true_peak: Option<f64>,
+
+    #[arg(long, default_value_t = false, conflicts_with_all = ["normalize", "lufs"])]
+    pub auto_gain: bool,
+
+    #[clap(long, value_enum, default_value_t=OutputStage::Hard)]
+    pub output_stage: OutputStage,
+
+    #[arg(long, default_value_t = 5.0)]
+    pub limiter_attack: f64,
+
+    #[arg(long, default_value_t = 100.0)]
+    pub limiter_release: f64,
+
+    #[arg(long, default_value_t = -0.1, allow_negative_numbers = true)]
+    pub limiter_ceiling: f64,
+
+    // repeatable; filters run in the order given
+    #[arg(long = "filter", value_parser = parse_filter)]
+    pub filters: Vec<AudioFilterParameters>,
+}
+
+// ---- RENDER SETTINGS ----
+// one combination of --format, --endian and --samplerate, rendered from each input
+#[derive(Clone, Debug)]
+pub struct Render {
+    pub format: SampleFormat,
+    pub endian: Endianness,
+    pub samplerate: u32,
+}
+
+impl Args {
+    // every combination of the listed --format, --endian and --samplerate values; formats
+    // that --endian doesn't affect are only rendered with the first one
+    pub fn renders(&self) -> Vec<Render> {
+        let mut renders = vec![];
+        for format in &self.formats {
+            let endians = if format.has_endian() {
+                &self.endians[..]
+            } else {
+                &self.endians[..1]
+            };
+            for endian in endians {
+                for samplerate in &self.samplerates {
+                    renders.push(Render {
+                        format: *format,
+                        endian: endian.clone(),
+                        samplerate: *samplerate,
+                    });
+                }
+            }
+        }
+        renders
+    }
+
+    // bits per sample of the decoded data
+    pub fn input_bits(&self, render: &Render) -> u16 {
+        match render.format {
+            SampleFormat::PackedInt | SampleFormat::PackedUint => self.bits as u16,
+            _ => render.format.bits(),
+        }
+    }
+
+    // format of the written WAV: --out-format if set, otherwise float32 for float input,
+    // or the smallest integer format that holds the input's bits
+    pub fn output_format(&self, render: &Render) -> OutFormat {
+        match self.out_format {
+            Some(out_format) => out_format,
+            None if render.format.is_float() => OutFormat::Float32,
+            None => match self.input_bits(render) {
+                0..=8 => OutFormat::Int8,
+                9..=16 => OutFormat::Int16,
+                17..=24 => OutFormat::Int24,
+                _ => OutFormat::Int32,
+            },
+        }
+    }
+
+    pub fn out_bits(&self, render: &Render) -> u16 {
+        self.output_format(render).bits()
+    }
 }
 
 #[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub enum SampleFormat {
+    Uint8,
     Int8,
+    Uint16,
     Int16,
+    Uint24,
     Int24,
+    Uint32,
     Int32,
+    PackedInt,
+    PackedUint,
+    Pdm,
+    Float16,
+    Bfloat16,
+    Float32,
+    Float64,
+    MuLaw,
+    ALaw,
     Vox,
+    AdpcmA,
+    AdpcmB,
+    Msm6258,
+    ImaAdpcm,
+    MsAdpcm,
+}
+
+impl SampleFormat {
+    // bits per sample of the decoded data
+    pub fn bits(&self) -> u16 {
+        match self {
+            SampleFormat::Uint8 | SampleFormat::Int8 => 8,
+            SampleFormat::Uint16 | SampleFormat::Int16 => 16,
+            SampleFormat::Uint24 | SampleFormat::Int24 => 24,
+            SampleFormat::Uint32 | SampleFormat::Int32 => 32,
+            // actual width is set by --bits; see Args::input_bits()
+            SampleFormat::PackedInt | SampleFormat::PackedUint => 32,
+            SampleFormat::Pdm => 16,
+            SampleFormat::Float16 | SampleFormat::Bfloat16 => 16,
+            SampleFormat::Float32 => 32,
+            SampleFormat::Float64 => 64,
+            // companded/compressed formats expand to 16-bit linear
+            SampleFormat::MuLaw
+            | SampleFormat::ALaw
+            | SampleFormat::Vox
+            | SampleFormat::AdpcmA
+            | SampleFormat::AdpcmB
+            | SampleFormat::Msm6258
+            | SampleFormat::ImaAdpcm
+            | SampleFormat::MsAdpcm => 16,
+        }
+    }
+
+    // whether samples span several bytes, so --endian changes how they're read; packed
+    // formats use --bit-order instead, and ima-adpcm/ms-adpcm are always little-endian
+    pub fn has_endian(&self) -> bool {
+        matches!(
+            self,
+            SampleFormat::Uint16
+                | SampleFormat::Int16
+                | SampleFormat::Uint24
+                | SampleFormat::Int24
+                | SampleFormat::Uint32
+                | SampleFormat::Int32
+                | SampleFormat::Float16
+                | SampleFormat::Bfloat16
+                | SampleFormat::Float32
+                | SampleFormat::Float64
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            SampleFormat::Float16
+                | SampleFormat::Bfloat16
+                | SampleFormat::Float32
+                | SampleFormat::Float64
+        )
+    }
+}
+
+// which end of each byte packed formats read first
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+// which half of each byte the nibble-stream ADPCM formats decode first
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum NibbleOrder {
+    HighFirst,
+    LowFirst,
+}
+
+// how float input is brought into range; NaN always becomes 0
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Sanitize {
+    Clamp,
+    Log,
+}
+
+// sample format of the written WAV
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum OutFormat {
+    Int8,
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+    Float64,
+}
+
+impl OutFormat {
+    pub fn bits(&self) -> u16 {
+        match self {
+            OutFormat::Int8 => 8,
+            OutFormat::Int16 => 16,
+            OutFormat::Int24 => 24,
+            OutFormat::Int32 | OutFormat::Float32 => 32,
+            OutFormat::Float64 => 64,
+        }
+    }
+
+    // IEEE float output is written as-is: no quantization, dither or clipping
+    pub fn is_float(&self) -> bool {
+        matches!(self, OutFormat::Float32 | OutFormat::Float64)
+    }
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -48,12 +379,310 @@ pub enum Endianness {
     Little,
     Big,
 }
-// pub const FMT_NUM_BITS: LazyLock<HashMap<SampleFormat, u16>> = LazyLock::new(|| {
-//     HashMap::from([
-//         (SampleFormat::Uint8, 8),
-//         (SampleFormat::Int16, 16),
-//         (SampleFormat::Int24, 24),
-//         (SampleFormat::Int32, 32),
-//         (SampleFormat::Vox, 8),
-//     ])
-// });
+
+// how channels are arranged in the input file
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // frames of one sample per channel, side by side
+    Interleaved,
+    // each channel in its own contiguous region
+    Planar,
+}
+
+// noise added when requantizing to the output bit depth
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    Rect,
+    Tpdf,
+    Shaped,
+}
+
+// what happens to samples beyond full scale before writing
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum OutputStage {
+    Hard,
+    Tanh,
+    Cubic,
+    Limiter,
+}
+
+// ---- BYTE POSITIONS ----
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Position {
+    Bytes(u64),
+    Percent(f64),
+}
+
+impl Position {
+    // byte count within a file (or region) of len bytes
+    pub fn resolve(&self, len: u64) -> u64 {
+        match self {
+            Position::Bytes(bytes) => *bytes,
+            Position::Percent(percent) => (len as f64 * percent / 100.0).round() as u64,
+        }
+    }
+}
+
+// either end may be left open, meaning the start or end of the file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: Option<Position>,
+    pub end: Option<Position>,
+}
+
+// ---- FILTER SPEC PARSER ----
+// "<type>:<fc>[:<q>[:<boost/cut dB>]]", e.g. "hpf2:30:0.7" or "peak:3000:2:-6"
+fn parse_filter(spec: &str) -> Result<AudioFilterParameters, String> {
+    let fields: Vec<&str> = spec.split(':').collect();
+
+    if fields.len() < 2 || fields.len() > 4 {
+        return Err(String::from(
+            "expected <type>:<fc>[:<q>[:<boost/cut dB>]], e.g. hpf2:30:0.7 or peak:3000:2:-6",
+        ));
+    }
+
+    let algorithm = match fields[0].to_lowercase().as_str() {
+        "lpf1p" => FilterAlgorithm::Lpf1P,
+        "lpf1" => FilterAlgorithm::Lpf1,
+        "hpf1" => FilterAlgorithm::Hpf1,
+        "lpf2" => FilterAlgorithm::Lpf2,
+        "hpf2" => FilterAlgorithm::Hpf2,
+        "bpf2" => FilterAlgorithm::Bpf2,
+        "bsf2" => FilterAlgorithm::Bsf2,
+        "lowshelf" => FilterAlgorithm::LowShelf,
+        "highshelf" => FilterAlgorithm::HighShelf,
+        "peak" => FilterAlgorithm::Peak,
+        other => return Err(format!("unknown filter type '{other}'")),
+    };
+
+    let parse_field = |field: &str, name: &str| -> Result<f64, String> {
+        field
+            .parse::<f64>()
+            .map_err(|e| format!("invalid {name} '{field}': {e}"))
+    };
+
+    let fc = parse_field(fields[1], "cutoff")?;
+    let q = match fields.get(2) {
+        Some(field) => parse_field(field, "Q")?,
+        None => 0.707,
+    };
+    let boost_cut_db = match fields.get(3) {
+        Some(field) => parse_field(field, "boost/cut")?,
+        None => 0.0,
+    };
+
+    if fc <= 0.0 {
+        return Err(format!("cutoff must be positive, got {fc}"));
+    }
+    if q <= 0.0 {
+        return Err(format!("Q must be positive, got {q}"));
+    }
+
+    Ok(AudioFilterParameters::new(algorithm, fc, q, boost_cut_db))
+}
+
+// ---- DECIMATION PARSER ----
+// pdm decimates by half in the CIC stage and half after the low-pass, so must be even
+fn parse_decimation(value: &str) -> Result<u32, String> {
+    let decimation: u32 = value
+        .parse()
+        .map_err(|e| format!("invalid decimation '{value}': {e}"))?;
+
+    if decimation < 2 || !decimation.is_multiple_of(2) {
+        return Err(format!(
+            "decimation must be an even number of at least 2, got {decimation}"
+        ));
+    }
+
+    Ok(decimation)
+}
+
+// ---- NAME TEMPLATE PARSER ----
+// must name a file inside --output
+fn parse_name_template(value: &str) -> Result<String, String> {
+    template::parse(value)?;
+
+    let path = std::path::Path::new(value);
+    if value.is_empty() || value.ends_with(['/', '\\']) {
+        return Err(String::from("name template must end in a file name"));
+    }
+    if path.has_root()
+        || path
+            .components()
+            .any(|component| component == std::path::Component::ParentDir)
+    {
+        return Err(format!(
+            "name template must stay inside the output folder, got '{value}'"
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+// ---- BYTE POSITION PARSER ----
+// "4096", "0x1000" or "25%"
+fn parse_position(value: &str) -> Result<Position, String> {
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent: f64 = percent
+            .parse()
+            .map_err(|e| format!("invalid percentage '{value}': {e}"))?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(format!("percentage must be from 0 to 100, got {value}"));
+        }
+        return Ok(Position::Percent(percent));
+    }
+
+    let bytes = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    bytes
+        .map(Position::Bytes)
+        .map_err(|e| format!("invalid byte position '{value}': {e}"))
+}
+
+// "<start>:<end>", either side optional, e.g. "0x200:50%" or "1000:"
+fn parse_range(value: &str) -> Result<ByteRange, String> {
+    let Some((start, end)) = value.split_once(':') else {
+        return Err(format!(
+            "expected <start>:<end>, e.g. 0x200:50% or 1000:, got '{value}'"
+        ));
+    };
+
+    let parse_side = |side: &str| -> Result<Option<Position>, String> {
+        if side.is_empty() {
+            Ok(None)
+        } else {
+            parse_position(side).map(Some)
+        }
+    };
+
+    Ok(ByteRange {
+        start: parse_side(start)?,
+        end: parse_side(end)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_templates_must_stay_inside_the_output_folder() {
+        for template in [
+            "{stem}",
+            "{relpath}_{rate}",
+            "a/b/{stem}",
+            "./{stem}",
+            "x..y",
+        ] {
+            assert!(parse_name_template(template).is_ok(), "{template}");
+        }
+
+        for template in [
+            "",
+            "../{stem}",
+            "a/../../{stem}",
+            "{relpath}/..",
+            "/tmp/{stem}",
+            "{stem}/",
+            "{stem",
+            "{nope}",
+        ] {
+            assert!(parse_name_template(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn byte_positions() {
+        let cases = [
+            ("4096", Ok(Position::Bytes(4096))),
+            ("0x1000", Ok(Position::Bytes(4096))),
+            ("0XfF", Ok(Position::Bytes(255))),
+            ("0%", Ok(Position::Percent(0.0))),
+            ("12.5%", Ok(Position::Percent(12.5))),
+            ("100%", Ok(Position::Percent(100.0))),
+            ("101%", Err(())),
+            ("-1%", Err(())),
+            ("-4", Err(())),
+            ("0x", Err(())),
+            ("0xg", Err(())),
+            ("1k", Err(())),
+            ("", Err(())),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_position(value).map_err(|_| ()), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn percentages_resolve_against_the_length() {
+        assert_eq!(Position::Percent(50.0).resolve(1001), 501);
+        assert_eq!(Position::Percent(100.0).resolve(1001), 1001);
+        assert_eq!(Position::Bytes(7).resolve(3), 7);
+    }
+
+    #[test]
+    fn byte_ranges() {
+        let range = |start, end| Ok(ByteRange { start, end });
+        let cases = [
+            (
+                "0x200:50%",
+                range(Some(Position::Bytes(512)), Some(Position::Percent(50.0))),
+            ),
+            ("1000:", range(Some(Position::Bytes(1000)), None)),
+            (":90%", range(None, Some(Position::Percent(90.0)))),
+            (":", range(None, None)),
+            ("12", Err(())),
+            ("1:2:3", Err(())),
+            ("x:10", Err(())),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_range(value).map_err(|_| ()), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn endian_is_only_crossed_with_multi_byte_formats() {
+        let args = Args::parse_from([
+            "data2audio",
+            "-f",
+            "int8,int16,vox",
+            "-e",
+            "little,big",
+            "-s",
+            "8000,44100",
+        ]);
+        let renders: Vec<(String, String, u32)> = args
+            .renders()
+            .iter()
+            .map(|render| {
+                (
+                    format!("{:?}", render.format),
+                    format!("{:?}", render.endian),
+                    render.samplerate,
+                )
+            })
+            .collect();
+
+        let expected = [
+            ("Int8", "Little", 8000),
+            ("Int8", "Little", 44100),
+            ("Int16", "Little", 8000),
+            ("Int16", "Little", 44100),
+            ("Int16", "Big", 8000),
+            ("Int16", "Big", 44100),
+            ("Vox", "Little", 8000),
+            ("Vox", "Little", 44100),
+        ]
+        .map(|(format, endian, rate)| (format.to_string(), endian.to_string(), rate));
+        assert_eq!(renders, expected);
+    }
+}
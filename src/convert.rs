@@ -1,30 +1,92 @@
-use std::fs;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
+use clap::ValueEnum;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use crate::biquad::{AudioFilter, AudioFilterParameters, FilterAlgorithm};
-use crate::cli::{Args, Endianness, SampleFormat};
+use crate::biquad::{AudioFilterParameters, FilterAlgorithm, FilterChain};
+use crate::cli::{Args, Layout, NibbleOrder, OutputStage, Render};
+use crate::decode::{decode_planes, decode_samples, deinterleave};
+use crate::dynamics;
+use crate::glob;
+use crate::normalize;
+use crate::template::{self, Fnv1a};
 use crate::vox;
-use crate::wav::write_file_as_wav;
+use crate::wav::{read_file_as_mono_i16, write_file_as_wav};
 
 pub fn convert_dir(args: &Args) {
-    WalkDir::new(&args.input)
-        .into_iter()
-        .par_bridge() // .par_bridge() is less effective than .into_par_iter(),
-        // but hard to parallelize file I/O with regular par iter
-        .filter_map(|entry| entry.ok())
-        .for_each(|entry| {
-            if let Ok(metadata) = entry.metadata()
-                && metadata.is_file()
-                && metadata.len() >= args.min
-            {
-                // ---- OUTPUT FILE ----
-                // write all files into output directory
-                let mut write_path = PathBuf::from(&args.output);
+    let files = input_files(args);
+    let renders = args.renders();
+    let selecting = args.offset.is_some() || args.length.is_some() || !args.ranges.is_empty();
+
+    // work out names in parallel, as {hash8} has to read each region
+    let mut jobs: Vec<(&PathBuf, Vec<Region>)> = files
+        .par_iter()
+        .enumerate()
+        .map(|(index, (input, file_len))| {
+            let regions = selections(*file_len, args)
+                .into_iter()
+                .filter_map(|(start, end)| {
+                    // an empty file without any selection is still written, as an empty WAV
+                    if start >= end.min(*file_len) && selecting {
+                        if start >= *file_len {
+                            eprintln!(
+                                "{:?}: offset {} is at or past the end of the file ({} bytes)",
+                                input, start, file_len
+                            );
+                        } else {
+                            eprintln!("{:?}: byte range {}-{} is empty", input, start, end);
+                        }
+                        return None;
+                    }
+                    let (start, end) = (start.min(*file_len), end.min(*file_len));
+
+                    let source = name_source(input, index, files.len(), (start, end), args)?;
+                    let outputs = renders
+                        .iter()
+                        .filter_map(|render| {
+                            let suffix = variant_suffix(args, render, (start, end));
+                            Some((render, output_path(&source, args, render, &suffix)?))
+                        })
+                        .collect();
+                    Some(Region {
+                        start,
+                        end,
+                        outputs,
+                    })
+                })
+                .collect();
+            (input, regions)
+        })
+        .collect();
+
+    // then claim them in input order, so collisions resolve the same way every run
+    let mut taken = HashSet::new();
+    for (_, write_path) in jobs
+        .iter_mut()
+        .flat_map(|(_, regions)| regions)
+        .flat_map(|region| &mut region.outputs)
+    {
+        *write_path = claim_path(write_path, ".wav", &mut taken);
+    }
+
+    jobs.par_iter().for_each(|(input, regions)| {
+        for region in regions {
+            // read once, then render each of args.renders() from it
+            let data = match read_region(input, region.start, region.end) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Error reading {:?} as .WAV file: {}", input, e);
+                    continue;
+                }
+            };
+
+            for (render, write_path) in &region.outputs {
                 // create output dir if doesn't exist - create_dir returns Result<T,E>, so match it and print if err
-                let out_dir = create_dir(&args.output);
+                let out_dir = create_dir(write_path);
                 match out_dir {
                     Ok(()) => {}
                     Err(e) => {
@@ -32,219 +94,484 @@ pub fn convert_dir(args: &Args) {
                     }
                 };
 
-                // entry.path().file_name() returns an Option, so if let Some() handles/extracts value
-                if let Some(file_name) = entry.path().file_name() {
-                    write_path.push(file_name);
+                convert_data(&data, input, write_path, args, render);
+            }
+        }
+    });
+}
+
+// bytes start..end of an input, and the outputs made from them: one per combination of
+// --format/--endian/--samplerate, each with where it goes (without extension, until claim_path)
+struct Region<'a> {
+    start: u64,
+    end: u64,
+    outputs: Vec<(&'a Render, PathBuf)>,
+}
 
-                    write_path.set_extension("");
+// ---- CONVERT ONE REGION ----
+fn convert_data(data: &[u8], input: &Path, write_path: &PathBuf, args: &Args, render: &Render) {
+    // make filters; fall back to 20 Hz low-cut if none given
+    let filter_params = if args.filters.is_empty() {
+        vec![AudioFilterParameters::new(
+            FilterAlgorithm::Hpf2,
+            20.0,
+            0.707,
+            0.0,
+        )]
+    } else {
+        args.filters.clone()
+    };
+    // --auto-gain sets the level after filtering instead
+    let gain_lin = if args.auto_gain {
+        1.0
+    } else {
+        f64::powf(10.0, args.gain / 20.0)
+    };
 
-                    if !(&args.append.is_empty()) {
-                        write_path = append_to_path(write_path, &args.append);
-                    }
+    // ---- CONVERT BASED ON SAMPLE FORMAT ----
+    // ---- CHANNELS ----
+    // --plane-offsets implies a planar layout; offsets count from the start of the region
+    let mut channels = if args.layout == Layout::Planar || !args.plane_offsets.is_empty() {
+        let plane_offsets: Vec<u64> = args
+            .plane_offsets
+            .iter()
+            .map(|offset| offset.resolve(data.len() as u64))
+            .collect();
+        if let Some(offset) = plane_offsets.iter().find(|o| **o >= data.len() as u64) {
+            eprintln!(
+                "{:?}: plane offset {} is past the end of the data",
+                input, offset
+            );
+            return;
+        }
+        decode_planes(data, args.channels as usize, &plane_offsets, args, render)
+    } else {
+        deinterleave(&decode_samples(data, args, render), args.channels as usize)
+    };
 
-                    // write_path.set_extension("wav");
-                    // using append prevents removing non-extension dot-separated
-                    // parts (which would also remove appended, if it exists)
-                    write_path = append_to_path(write_path, ".wav");
+    if !args.raw {
+        for channel in &mut channels {
+            // each channel keeps its own filter state
+            let mut filter = FilterChain::new(&filter_params, render.samplerate);
+            for sample in channel.iter_mut() {
+                *sample = filter.process_sample(*sample * gain_lin);
+            }
+        }
+    }
 
-                    let mut data: Vec<u8> = vec![];
-                    match fs::read(entry.path()) {
-                        Ok(file) => {
-                            data = file;
-                        }
-                        Err(e) => {
-                            eprintln!("Error reading {:?} as .WAV file: {}", entry.path(), e);
-                        }
-                    };
-
-                    // make filter
-                    let filter_params =
-                        AudioFilterParameters::new(FilterAlgorithm::Hpf2, 20.0, 0.707, 0.0);
-                    let mut filter = AudioFilter::new(&filter_params, args.samplerate);
-                    filter.calculate_filter_coeffs();
-                    let gain_lin = f64::powf(10.0, args.gain / 20.0);
-
-                    // ---- CONVERT BASED ON SAMPLE FORMAT ----
-                    match args.format {
-                        SampleFormat::Int8 => {
-                            let mut formatted_data: Vec<i8> = data
-                                .iter()
-                                // needs to be i8 to satisfy Sample trait bound
-                                .map(|chunk| ((*chunk as i16) - 128) as i8)
-                                .collect();
-
-                            if !args.raw {
-                                for sample in &mut formatted_data {
-                                    *sample =
-                                        (filter.process_sample((*sample as f64) * gain_lin)) as i8;
-                                }
-                            }
-
-                            match write_file_as_wav(&formatted_data, &write_path, args) {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    eprintln!("{e}")
-                                }
-                            };
-                        }
-                        SampleFormat::Int16 => {
-                            let mut formatted_data: Vec<i16> = data
-                                .chunks_exact(2)
-                                .map(|chunk| {
-                                    match &args.endian {
-                                        Endianness::Big => {
-                                            // from_le_bytes() takes array of bytes and converts to a single little-endian integer
-                                            i16::from_be_bytes(
-                                                chunk
-                                                    .try_into()
-                                                    .expect("Could not import as 16-bit"),
-                                            )
-                                        }
-                                        Endianness::Little => {
-                                            // from_le_bytes() takes array of bytes and converts to a single little-endian integer
-                                            i16::from_le_bytes(
-                                                chunk
-                                                    .try_into()
-                                                    .expect("Could not import as 16-bit"),
-                                            )
-                                        }
-                                    }
-                                })
-                                .collect();
-
-                            if !args.raw {
-                                for sample in &mut formatted_data {
-                                    *sample =
-                                        (filter.process_sample((*sample as f64) * gain_lin)) as i16;
-                                }
-                            }
-
-                            match write_file_as_wav(&formatted_data, &write_path, args) {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    eprintln!("{e}")
-                                }
-                            };
-                        }
-                        SampleFormat::Int24 => {
-                            let mut formatted_data: Vec<i32> = data
-                                .chunks_exact(3)
-                                .map(|chunk| {
-                                    // get values from chunks_exact(3), put in array
-                                    let data_bytes: [u8; 3] =
-                                        chunk.try_into().expect("Could not import as 24-bit");
-                                    // no i24, so we add this 0x00 to fill out hi byte in i32
-                                    let padding_byte: [u8; 1] = [0x00];
-                                    // copy to "joined" from low/hi parts as slices
-                                    let mut joined: [u8; 4] = [0; 4];
-
-                                    match &args.endian {
-                                        Endianness::Big => {
-                                            joined[..1].copy_from_slice(&padding_byte);
-                                            joined[1..].copy_from_slice(&data_bytes);
-
-                                            i32::from_be_bytes(joined)
-                                        }
-                                        Endianness::Little => {
-                                            joined[3..].copy_from_slice(&padding_byte);
-                                            joined[..3].copy_from_slice(&data_bytes);
-
-                                            i32::from_le_bytes(joined)
-                                        }
-                                    }
-                                })
-                                .collect();
-
-                            if !args.raw {
-                                for sample in &mut formatted_data {
-                                    *sample =
-                                        (filter.process_sample((*sample as f64) * gain_lin)) as i32;
-                                }
-                            }
-
-                            match write_file_as_wav(&formatted_data, &write_path, args) {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    eprintln!("{e}")
-                                }
-                            };
-                        }
-                        SampleFormat::Int32 => {
-                            let mut formatted_data: Vec<i32> = data
-                                .chunks_exact(4)
-                                .map(|chunk| {
-                                    match &args.endian {
-                                        Endianness::Big => {
-                                            // bit-shift based on using 16-bit wav at output
-                                            i32::from_le_bytes(
-                                                chunk
-                                                    .try_into()
-                                                    .expect("Could not import as 32-bit"),
-                                            )
-                                        }
-                                        Endianness::Little => {
-                                            // bit-shift based on using 16-bit wav at output
-                                            i32::from_le_bytes(
-                                                chunk
-                                                    .try_into()
-                                                    .expect("Could not import as 32-bit"),
-                                            )
-                                        }
-                                    }
-                                })
-                                .collect();
-
-                            if !args.raw {
-                                for sample in &mut formatted_data {
-                                    *sample =
-                                        (filter.process_sample((*sample as f64) * gain_lin)) as i32;
-                                }
-                            }
-
-                            match write_file_as_wav(&formatted_data, &write_path, args) {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    eprintln!("{e}")
-                                }
-                            };
-                        }
-                        SampleFormat::Vox => {
-                            let mut formatted_data: Vec<i16> = Vec::new();
-                            let mut vox_state = vox::VoxState::new();
-                            data.iter()
-                                // using for_each and...
-                                .for_each(|chunk| {
-                                    // start with highest 4 bits by right-shifting
-                                    // & 0b1111 selects lowest 4
-                                    for nibble in [chunk >> 4, chunk & 0b1111].iter() {
-                                        formatted_data.push(vox_state.vox_decode(nibble));
-                                    }
-                                });
-
-                            if !args.raw {
-                                for sample in &mut formatted_data {
-                                    *sample =
-                                        (filter.process_sample((*sample as f64) * gain_lin)) as i16;
-                                }
-                            }
-
-                            match write_file_as_wav(&formatted_data, &write_path, args) {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    eprintln!("{e}")
-                                }
-                            };
-                        }
-                    };
+    // ---- NORMALIZE ----
+    // second pass, now that the filtered peak is known
+    if let Some(target_dbfs) = args.normalize {
+        normalize::peak_normalize(&mut channels, target_dbfs, args.out_bits(render));
+    } else if let Some(target_lufs) = args.lufs {
+        normalize::loudness_normalize(
+            &mut channels,
+            render.samplerate,
+            target_lufs,
+            args.true_peak,
+        );
+    } else if args.auto_gain {
+        dynamics::auto_headroom(&mut channels, args.out_bits(render));
+    }
+
+    // ---- OUTPUT STAGE ----
+    let bits = args.out_bits(render);
+    match args.output_stage {
+        // saturated by the integer conversion in write_file_as_wav
+        OutputStage::Hard => {}
+        OutputStage::Tanh => dynamics::soft_clip_tanh(&mut channels, bits),
+        OutputStage::Cubic => dynamics::soft_clip_cubic(&mut channels, bits),
+        OutputStage::Limiter => dynamics::limit(
+            &mut channels,
+            render.samplerate,
+            args.limiter_attack,
+            args.limiter_release,
+            args.limiter_ceiling,
+            bits,
+        ),
+    }
+
+    // ---- CLIPPING ----
+    // report what the integer conversion in write_file_as_wav will saturate;
+    // float output keeps samples beyond full scale, so never clips
+    if !args.output_format(render).is_float() {
+        let clipped = dynamics::count_clipped(&channels, bits);
+        let total: usize = channels.iter().map(|channel| channel.len()).sum();
+        if clipped > 0 {
+            eprintln!(
+                "{:?}: {} of {} samples clipped ({:.3}%)",
+                write_path,
+                clipped,
+                total,
+                100.0 * clipped as f64 / total as f64
+            );
+        }
+    }
+
+    match write_file_as_wav(&channels, write_path, args, render) {
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("{e}")
+        }
+    };
+}
+
+// ---- BYTE SELECTION ----
+// (start, end) byte regions to convert, as asked for (so either may be past the end of
+// the file, for messages; clamp before reading): one per --range, otherwise the one set
+// by --offset and --length (default: the whole file)
+fn selections(file_len: u64, args: &Args) -> Vec<(u64, u64)> {
+    if args.ranges.is_empty() {
+        let start = args.offset.map_or(0, |offset| offset.resolve(file_len));
+        let end = match args.length {
+            Some(length) => start.saturating_add(length.resolve(file_len)),
+            None => file_len,
+        };
+        vec![(start, end)]
+    } else {
+        args.ranges
+            .iter()
+            .map(|range| {
+                let start = range.start.map_or(0, |start| start.resolve(file_len));
+                let end = range.end.map_or(file_len, |end| end.resolve(file_len));
+                (start, end)
+            })
+            .collect()
+    }
+}
+
+// read only bytes start..end, so huge files (e.g. disk images) aren't loaded in full
+fn read_region(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut data = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// ---- ENCODE WAVs TO VOX ----
+pub fn encode_dir(args: &Args) {
+    let files: Vec<(PathBuf, u64)> = input_files(args)
+        .into_iter()
+        .filter(|(input, _)| {
+            input
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+
+    // only --samplerate can matter (for naming), and only the first one
+    let render = &args.renders()[0];
+    let named: Vec<(&PathBuf, Option<PathBuf>)> = files
+        .par_iter()
+        .enumerate()
+        .map(|(index, (input, file_len))| {
+            let base = name_source(input, index, files.len(), (0, *file_len), args)
+                .and_then(|source| output_path(&source, args, render, ""));
+            (input, base)
+        })
+        .collect();
+
+    let mut taken = HashSet::new();
+    let jobs: Vec<(&PathBuf, PathBuf)> = named
+        .into_iter()
+        .filter_map(|(input, base)| Some((input, claim_path(&base?, ".vox", &mut taken))))
+        .collect();
+
+    jobs.par_iter().for_each(|(input, write_path)| {
+        match create_dir(write_path) {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("{e}")
+            }
+        };
+
+        let samples = match read_file_as_mono_i16(input) {
+            Ok((samples, _)) => samples,
+            Err(e) => {
+                eprintln!("Error reading {:?} as .WAV file: {}", input, e);
+                return;
+            }
+        };
+
+        let mut vox_state = vox::VoxState::new();
+        let order = args.nibble_order.unwrap_or(NibbleOrder::HighFirst);
+        // two samples per byte; an odd final sample is padded with a 0 nibble
+        let encoded: Vec<u8> = samples
+            .chunks(2)
+            .map(|pair| {
+                let first = vox_state.vox_encode(&pair[0]);
+                let second = pair.get(1).map_or(0, |s| vox_state.vox_encode(s));
+                match order {
+                    NibbleOrder::HighFirst => (first << 4) | second,
+                    NibbleOrder::LowFirst => (second << 4) | first,
                 }
+            })
+            .collect();
+
+        match fs::write(write_path, encoded) {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("Error writing {:?}: {}", write_path, e);
             }
+        };
+    });
+}
+
+// ---- INPUT FILES ----
+// every file under --input that passes the filters below, with its size; walked in sorted
+// order so outputs (and collision renames) come out the same on every run
+fn input_files(args: &Args) -> Vec<(PathBuf, u64)> {
+    let root = Path::new(&args.input);
+
+    let mut walk = WalkDir::new(root)
+        .follow_links(args.follow_links)
+        .sort_by_file_name();
+    if let Some(max_depth) = args.max_depth {
+        walk = walk.max_depth(max_depth);
+    }
+
+    walk.into_iter()
+        // don't walk into skipped folders at all (e.g. .git, node_modules); --input itself is never skipped
+        .filter_entry(|entry| entry.depth() == 0 || !is_skipped(entry.path(), root, args))
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_file()
+                && metadata.len() >= args.min
+                && args.max.is_none_or(|max| metadata.len() <= max)
+                && is_included(entry.path(), root, args)
+            {
+                Some((entry.into_path(), metadata.len()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// --skip-hidden and --exclude, for files and folders
+fn is_skipped(path: &Path, root: &Path, args: &Args) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    let relative = relative_path(path, root);
+
+    (args.skip_hidden && hidden)
+        || args
+            .exclude
+            .iter()
+            .any(|pattern| glob::matches(pattern, &relative))
+}
+
+// --include, --include-ext and --exclude-ext, for files
+fn is_included(path: &Path, root: &Path, args: &Args) -> bool {
+    let relative = relative_path(path, root);
+    let ext = path
+        .extension()
+        .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
+    let listed = |list: &[String]| {
+        list.iter()
+            .any(|listed| listed.trim_start_matches('.').to_lowercase() == ext)
+    };
+
+    (args.include.is_empty()
+        || args
+            .include
+            .iter()
+            .any(|pattern| glob::matches(pattern, &relative)))
+        && (args.include_ext.is_empty() || listed(&args.include_ext))
+        && !listed(&args.exclude_ext)
+}
+
+// path below --input, '/'-separated, for matching globs; just the file name if --input is a file
+fn relative_path(path: &Path, root: &Path) -> String {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => Path::new(path.file_name().unwrap_or_default()),
+    };
+
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// ---- OUTPUT FILE ----
+// what an output's name is made from, besides the settings
+struct NameSource<'a> {
+    input: &'a Path,
+    // 1-based position among all inputs, zero-padded so names sort in order
+    index: String,
+    start: u64,
+    // only worked out if --name-template uses {hash8}
+    hash8: String,
+}
+
+fn name_source<'a>(
+    input: &'a Path,
+    index: usize,
+    count: usize,
+    (start, end): (u64, u64),
+    args: &Args,
+) -> Option<NameSource<'a>> {
+    let hash8 = match &args.name_template {
+        Some(name_template) if template::uses(name_template, "hash8") => {
+            match hash_region(input, start, end) {
+                Ok(hash) => format!("{hash:08x}"),
+                Err(e) => {
+                    eprintln!("Error reading {:?}: {}", input, e);
+                    return None;
+                }
+            }
+        }
+        _ => String::new(),
+    };
+
+    Some(NameSource {
+        input,
+        index: format!("{:0width$}", index + 1, width = count.to_string().len()),
+        start,
+        hash8,
+    })
+}
+
+// the input's path relative to --input, mirrored under --output (just its file name with
+// --flat), plus --append; or --name-template filled in instead. then suffix; the
+// extension is added by claim_path
+fn output_path(source: &NameSource, args: &Args, render: &Render, suffix: &str) -> Option<PathBuf> {
+    // file_name() returns an Option, so ? returns None for paths like ".."
+    let file_name = source.input.file_name()?;
+
+    // --input may also be a single file, which has no relative path
+    let relative = match source.input.strip_prefix(&args.input) {
+        Ok(relative) if !args.flat && !relative.as_os_str().is_empty() => relative,
+        _ => Path::new(file_name),
+    };
+
+    let mut write_path = PathBuf::from(&args.output);
+
+    if let Some(name_template) = &args.name_template {
+        let name = template::render(name_template, |placeholder| match placeholder {
+            "stem" => Path::new(file_name)
+                .with_extension("")
+                .display()
+                .to_string(),
+            "ext" => Path::new(file_name)
+                .extension()
+                .map_or(String::new(), |ext| ext.to_string_lossy().to_string()),
+            "relpath" => relative.with_extension("").display().to_string(),
+            "format" => value_name(&render.format),
+            "endian" => value_name(&render.endian),
+            "rate" => render.samplerate.to_string(),
+            "offset" => source.start.to_string(),
+            "hash8" => source.hash8.clone(),
+            "index" => source.index.clone(),
+            _ => String::new(),
         });
+
+        write_path.push(name);
+    } else {
+        write_path.push(relative);
+
+        write_path.set_extension("");
+
+        if !(&args.append.is_empty()) {
+            write_path = append_to_path(write_path, &args.append);
+        }
+    }
+
+    Some(append_to_path(write_path, suffix))
+}
+
+// ---- DISTINCT NAMES ----
+// tag each output with whatever can differ between outputs of the same input: its
+// --range, and each of --format/--endian/--samplerate given more than once; unless
+// --name-template already includes it
+fn variant_suffix(args: &Args, render: &Render, (start, end): (u64, u64)) -> String {
+    let named = |placeholder: &str| {
+        args.name_template
+            .as_ref()
+            .is_some_and(|name_template| template::uses(name_template, placeholder))
+    };
+
+    let mut suffix = String::new();
+    if !args.ranges.is_empty() && !named("offset") {
+        suffix.push_str(&format!("_{start}-{end}"));
+    }
+    if args.formats.len() > 1 && !named("format") {
+        suffix.push_str(&format!("_{}", value_name(&render.format)));
+    }
+    if args.endians.len() > 1 && render.format.has_endian() && !named("endian") {
+        suffix.push_str(&format!("_{}", value_name(&render.endian)));
+    }
+    if args.samplerates.len() > 1 && !named("rate") {
+        suffix.push_str(&format!("_{}", render.samplerate));
+    }
+    suffix
+}
+
+// a setting as spelled on the command line, e.g. "mu-law"
+fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or(String::new(), |value| value.get_name().to_string())
+}
+
+// {hash8} of bytes start..end, read in blocks rather than all at once
+fn hash_region(path: &Path, start: u64, end: u64) -> std::io::Result<u32> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut region = file.take(end - start);
+    let mut hash = Fnv1a::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let len = region.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hash.update(&buffer[..len]);
+    }
+
+    Ok(hash.finish())
+}
+
+// ---- NAME COLLISIONS ----
+// e.g. "a.bin" and "a.txt", or two "data.bin" with --flat; later ones get _2, _3, ...
+// rather than racing to overwrite each other
+fn claim_path(base: &Path, extension: &str, taken: &mut HashSet<PathBuf>) -> PathBuf {
+    // write_path.set_extension("wav");
+    // using append prevents removing non-extension dot-separated
+    // parts (which would also remove appended, if it exists)
+    let wanted = append_to_path(base.to_path_buf(), extension);
+    let mut write_path = wanted.clone();
+
+    let mut n = 2;
+    while !taken.insert(write_path.clone()) {
+        write_path = append_to_path(base.to_path_buf(), &format!("_{n}{extension}"));
+        n += 1;
+    }
+
+    if write_path != wanted {
+        eprintln!(
+            "{:?} would overwrite another output, writing {:?} instead",
+            wanted, write_path
+        );
+    }
+
+    write_path
 }
 
 // ---- WRITING WAVs ----
-fn create_dir(dir: &str) -> std::io::Result<()> {
-    // create_dir_all - like multiple mkdir calls
-    fs::create_dir_all(dir)?;
+// create the folder(s) an output file goes in
+fn create_dir(write_path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = write_path.parent() {
+        // create_dir_all - like multiple mkdir calls
+        fs::create_dir_all(dir)?;
+    }
     Ok(())
 }
 
@@ -255,3 +582,185 @@ fn append_to_path(p: PathBuf, s: &str) -> PathBuf {
     p.push(s);
     p.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn args(options: &[&str]) -> Args {
+        Args::parse_from([&["data2audio"], options].concat())
+    }
+
+    #[test]
+    fn byte_selections() {
+        // options, file length, expected (start, end) regions
+        type Case<'a> = (&'a [&'a str], u64, Vec<(u64, u64)>);
+        let cases: [Case; 12] = [
+            (&[], 1000, vec![(0, 1000)]),
+            (&["--offset", "0x10"], 1000, vec![(16, 1000)]),
+            (
+                &["--offset", "100", "--length", "50"],
+                1000,
+                vec![(100, 150)],
+            ),
+            (
+                &["--offset", "25%", "--length", "50%"],
+                1000,
+                vec![(250, 750)],
+            ),
+            // past the end of the file: kept as asked for, and clamped by convert_dir
+            (
+                &["--offset", "900", "--length", "500"],
+                1000,
+                vec![(900, 1400)],
+            ),
+            (
+                &["--length", "0xffffffffffffffff", "--offset", "1"],
+                1000,
+                vec![(1, u64::MAX)],
+            ),
+            (&["--offset", "2000"], 1000, vec![(2000, 1000)]),
+            (&["--range", "0x100:"], 1000, vec![(256, 1000)]),
+            (
+                &["--range", ":10%", "--range", "90%:"],
+                1000,
+                vec![(0, 100), (900, 1000)],
+            ),
+            (&["--range", "500:5000"], 1000, vec![(500, 5000)]),
+            // start >= end is kept as is, for convert_dir to report
+            (&["--range", "600:400"], 1000, vec![(600, 400)]),
+            (&["--range", "50%:50%"], 1000, vec![(500, 500)]),
+        ];
+
+        for (options, file_len, expected) in cases {
+            assert_eq!(
+                selections(file_len, &args(options)),
+                expected,
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ranges_cannot_be_combined_with_offset_or_length() {
+        for options in [
+            ["--range", "0:10", "--offset", "5"],
+            ["--range", "0:10", "--length", "5"],
+        ] {
+            assert!(Args::try_parse_from([&["data2audio"][..], &options].concat()).is_err());
+        }
+    }
+
+    #[test]
+    fn excluded_and_hidden_folders_are_not_walked_into() {
+        let root = std::env::temp_dir().join(format!("data2audio-walk-{}", std::process::id()));
+        for file in [
+            "keep.bin",
+            "a/keep.bin",
+            "a/node_modules/skip.bin",
+            "node_modules/b/skip.bin",
+            "a/build/skip.bin",
+            "build/keep.bin",
+            ".git/skip.bin",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, [0; 4]).unwrap();
+        }
+
+        let input = root.to_string_lossy().to_string();
+        // the files inside don't match these patterns themselves, so only pruning the
+        // folders can leave them out
+        let found: Vec<String> = input_files(&args(&[
+            "-i",
+            &input,
+            "--exclude",
+            "node_modules",
+            "--exclude",
+            "a/build",
+            "--skip-hidden",
+        ]))
+        .into_iter()
+        .map(|(path, _)| relative_path(&path, &root))
+        .collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(found, ["a/keep.bin", "build/keep.bin", "keep.bin"]);
+    }
+
+    #[test]
+    fn colliding_names_are_numbered_in_order() {
+        let mut taken = HashSet::new();
+        let mut claim = |base: &str, extension: &str| {
+            claim_path(Path::new(base), extension, &mut taken)
+                .display()
+                .to_string()
+        };
+
+        assert_eq!(claim("out/a", ".wav"), "out/a.wav");
+        // e.g. from a.txt
+        assert_eq!(claim("out/a", ".wav"), "out/a_2.wav");
+        assert_eq!(claim("out/a", ".wav"), "out/a_3.wav");
+        // a different extension doesn't collide
+        assert_eq!(claim("out/a", ".vox"), "out/a.vox");
+        // nor does the same name in another folder
+        assert_eq!(claim("out/b/a", ".wav"), "out/b/a.wav");
+        // a name that already looks numbered is just taken first
+        assert_eq!(claim("out/c_2", ".wav"), "out/c_2.wav");
+        assert_eq!(claim("out/c", ".wav"), "out/c.wav");
+        assert_eq!(claim("out/c", ".wav"), "out/c_3.wav");
+    }
+
+    #[test]
+    fn same_names_in_different_folders_get_distinct_outputs() {
+        let root = std::env::temp_dir().join(format!("data2audio-names-{}", std::process::id()));
+        let input = root.join("in");
+        // different lengths, to tell which output came from which input
+        for (file, len) in [("a/data.bin", 100), ("b/data.bin", 200), ("data.txt", 300)] {
+            let path = input.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0; len]).unwrap();
+        }
+
+        let outputs = |options: &[&str]| {
+            let output = root.join("out");
+            let [input, output] = [&input, &output].map(|dir| dir.to_string_lossy().to_string());
+            convert_dir(&args(&[&["-i", &input, "-o", &output], options].concat()));
+
+            let mut found: Vec<(String, u64)> = WalkDir::new(&output)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| {
+                    let len = entry.metadata().unwrap().len();
+                    (relative_path(entry.path(), Path::new(&output)), len)
+                })
+                .collect();
+            found.sort();
+            fs::remove_dir_all(root.join("out")).unwrap();
+            found
+        };
+
+        // 8-bit in and out: a 44-byte header, then one byte per input byte
+        assert_eq!(
+            outputs(&["-f", "int8", "-F", "int8"]),
+            [
+                ("a/data.wav".to_string(), 144),
+                ("b/data.wav".to_string(), 244),
+                ("data.wav".to_string(), 344),
+            ]
+        );
+        // numbered in input order: a/, then b/, then the top-level file
+        assert_eq!(
+            outputs(&["-f", "int8", "-F", "int8", "--flat"]),
+            [
+                ("data.wav".to_string(), 144),
+                ("data_2.wav".to_string(), 244),
+                ("data_3.wav".to_string(), 344),
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use crate::adpcm;
+use crate::bitstream;
+use crate::cli::{Args, Endianness, NibbleOrder, Render, SampleFormat, Sanitize};
+use crate::g711;
+use crate::pdm;
+use crate::vox;
+
+// ---- DECODE SAMPLES ----
+// returns samples scaled to [-1.0, 1.0) relative to the format's full scale
+pub fn decode_samples(data: &[u8], args: &Args, render: &Render) -> Vec<f64> {
+    let full_scale = f64::powi(2.0, args.input_bits(render) as i32 - 1);
+
+    match render.format {
+        SampleFormat::Uint8 => decode_int(data, 1, &render.endian, false),
+        SampleFormat::Int8 => decode_int(data, 1, &render.endian, true),
+        SampleFormat::Uint16 => decode_int(data, 2, &render.endian, false),
+        SampleFormat::Int16 => decode_int(data, 2, &render.endian, true),
+        SampleFormat::Uint24 => decode_int(data, 3, &render.endian, false),
+        SampleFormat::Int24 => decode_int(data, 3, &render.endian, true),
+        SampleFormat::Uint32 => decode_int(data, 4, &render.endian, false),
+        SampleFormat::Int32 => decode_int(data, 4, &render.endian, true),
+        SampleFormat::PackedInt | SampleFormat::PackedUint => {
+            let signed = render.format == SampleFormat::PackedInt;
+            bitstream::unpack(data, args.bits, args.bit_order)
+                .into_iter()
+                .map(|value| to_signed(value, args.bits, signed) as f64 / full_scale)
+                .collect()
+        }
+        SampleFormat::Pdm => pdm::decimate(
+            &bitstream::unpack(data, 1, args.bit_order),
+            args.decimation as usize,
+            render.samplerate,
+        ),
+        SampleFormat::Float16
+        | SampleFormat::Bfloat16
+        | SampleFormat::Float32
+        | SampleFormat::Float64 => decode_float(data, render.format, &render.endian, args.sanitize),
+        SampleFormat::MuLaw => data
+            .iter()
+            .map(|byte| g711::mulaw_decode(*byte) as f64 / full_scale)
+            .collect(),
+        SampleFormat::ALaw => data
+            .iter()
+            .map(|byte| g711::alaw_decode(*byte) as f64 / full_scale)
+            .collect(),
+        // blocks are decoded independently; a short final block still decodes if it has a header
+        SampleFormat::ImaAdpcm => data
+            .chunks(args.block_size as usize)
+            .flat_map(adpcm::ima_decode_block)
+            .map(|sample| sample as f64 / full_scale)
+            .collect(),
+        SampleFormat::MsAdpcm => data
+            .chunks(args.block_size as usize)
+            .flat_map(adpcm::ms_decode_block)
+            .map(|sample| sample as f64 / full_scale)
+            .collect(),
+        // nibble-stream ADPCM; each variant has its own default nibble order
+        SampleFormat::Vox => {
+            let mut vox_state = vox::VoxState::new();
+            let order = args.nibble_order.unwrap_or(NibbleOrder::HighFirst);
+            decode_nibbles(data, order, |nibble| vox_state.vox_decode(&nibble))
+        }
+        SampleFormat::AdpcmA => {
+            let mut adpcm_a_state = vox::AdpcmAState::new();
+            let order = args.nibble_order.unwrap_or(NibbleOrder::HighFirst);
+            decode_nibbles(data, order, |nibble| adpcm_a_state.adpcm_a_decode(&nibble))
+        }
+        SampleFormat::AdpcmB => {
+            let mut adpcm_b_state = vox::AdpcmBState::new();
+            let order = args.nibble_order.unwrap_or(NibbleOrder::HighFirst);
+            decode_nibbles(data, order, |nibble| adpcm_b_state.adpcm_b_decode(&nibble))
+        }
+        SampleFormat::Msm6258 => {
+            let mut msm6258_state = vox::Msm6258State::new();
+            let order = args.nibble_order.unwrap_or(NibbleOrder::LowFirst);
+            decode_nibbles(data, order, |nibble| msm6258_state.msm6258_decode(&nibble))
+        }
+    }
+}
+
+// ---- CHANNELS ----
+// split interleaved samples (frames of one sample per channel) into one Vec per channel;
+// a partial frame at the end is dropped
+pub fn deinterleave(samples: &[f64], num_channels: usize) -> Vec<Vec<f64>> {
+    let mut channels: Vec<Vec<f64>> =
+        vec![Vec::with_capacity(samples.len() / num_channels); num_channels];
+
+    for frame in samples.chunks_exact(num_channels) {
+        for (channel, sample) in channels.iter_mut().zip(frame) {
+            channel.push(*sample);
+        }
+    }
+
+    channels
+}
+
+// ---- PLANAR CHANNELS ----
+// one channel per region of the file, each decoded on its own: from each of the given
+// byte offsets to the end of the file or, with no offsets, num_channels equal parts of
+// whole samples (empty if the file is too short); channels are trimmed to the shortest
+pub fn decode_planes(
+    data: &[u8],
+    num_channels: usize,
+    offsets: &[u64],
+    args: &Args,
+    render: &Render,
+) -> Vec<Vec<f64>> {
+    let regions: Vec<&[u8]> = if offsets.is_empty() {
+        let unit = sample_unit_len(args, render);
+        let region_len = data.len() / num_channels / unit * unit;
+        (0..num_channels)
+            .map(|n| &data[n * region_len..(n + 1) * region_len])
+            .collect()
+    } else {
+        offsets
+            .iter()
+            .map(|offset| data.get(*offset as usize..).unwrap_or(&[]))
+            .collect()
+    };
+
+    let mut channels: Vec<Vec<f64>> = regions
+        .iter()
+        .map(|region| decode_samples(region, args, render))
+        .collect();
+
+    let len = channels
+        .iter()
+        .map(|channel| channel.len())
+        .min()
+        .unwrap_or(0);
+    for channel in &mut channels {
+        channel.truncate(len);
+    }
+
+    channels
+}
+
+// the fewest bytes holding a whole number of samples, so that a region of a multiple of
+// this many bytes starts and ends on sample boundaries
+fn sample_unit_len(args: &Args, render: &Render) -> usize {
+    match render.format {
+        SampleFormat::Uint16 | SampleFormat::Int16 => 2,
+        SampleFormat::Uint24 | SampleFormat::Int24 => 3,
+        SampleFormat::Uint32 | SampleFormat::Int32 => 4,
+        SampleFormat::Float16 | SampleFormat::Bfloat16 => 2,
+        SampleFormat::Float32 => 4,
+        SampleFormat::Float64 => 8,
+        SampleFormat::ImaAdpcm | SampleFormat::MsAdpcm => args.block_size as usize,
+        // e.g. 12-bit samples come in whole pairs of 3 bytes
+        SampleFormat::PackedInt | SampleFormat::PackedUint => {
+            (args.bits / gcd(args.bits, 8)) as usize
+        }
+        _ => 1,
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// ---- INTEGER PCM ----
+// 1-4 byte integers; signed data is two's complement, unsigned data is offset binary
+// (midpoint = silence), so subtracting the midpoint puts both in the same signed range
+fn decode_int(data: &[u8], num_bytes: usize, endian: &Endianness, signed: bool) -> Vec<f64> {
+    let bits = 8 * num_bytes as u32;
+    let full_scale = f64::powi(2.0, bits as i32 - 1);
+
+    data.chunks_exact(num_bytes)
+        .map(|chunk| {
+            let value = assemble_bytes(chunk, endian) as u32;
+            to_signed(value, bits, signed) as f64 / full_scale
+        })
+        .collect()
+}
+
+// bits-wide value in the low bits of a u32 -> signed value in [-2^(bits-1), 2^(bits-1))
+fn to_signed(value: u32, bits: u32, signed: bool) -> i64 {
+    if signed {
+        // shift the sign bit up to bit 31, then arithmetic-shift back to sign-extend
+        ((value << (32 - bits)) as i32 >> (32 - bits)) as i64
+    } else {
+        value as i64 - (1i64 << (bits - 1))
+    }
+}
+
+// ---- IEEE FLOAT ----
+// half, bfloat16, single and double precision; arbitrary bytes give NaN, infinities and
+// huge exponents, so every value is sanitized into [-1.0, 1.0]
+fn decode_float(
+    data: &[u8],
+    format: SampleFormat,
+    endian: &Endianness,
+    sanitize: Sanitize,
+) -> Vec<f64> {
+    let (num_bytes, max_finite) = match format {
+        SampleFormat::Float16 => (2, 65504.0),
+        SampleFormat::Bfloat16 => (2, f32::MAX as f64),
+        SampleFormat::Float32 => (4, f32::MAX as f64),
+        _ => (8, f64::MAX),
+    };
+    // log-compressed magnitude of the largest finite value maps to 1.0
+    let log_max = f64::ln_1p(max_finite);
+
+    data.chunks_exact(num_bytes)
+        .map(|chunk| {
+            let bits = assemble_bytes(chunk, endian);
+            let value = match format {
+                SampleFormat::Float16 => f16_to_f64(bits as u16),
+                // bfloat16 is the top half of an f32
+                SampleFormat::Bfloat16 => f32::from_bits((bits as u32) << 16) as f64,
+                SampleFormat::Float32 => f32::from_bits(bits as u32) as f64,
+                _ => f64::from_bits(bits),
+            };
+
+            if value.is_nan() {
+                return 0.0;
+            }
+
+            match sanitize {
+                Sanitize::Clamp => value.clamp(-1.0, 1.0),
+                // infinity stays infinite through ln_1p, so clamp that to full scale too
+                Sanitize::Log => {
+                    (value.signum() * f64::ln_1p(value.abs()) / log_max).clamp(-1.0, 1.0)
+                }
+            }
+        })
+        .collect()
+}
+
+// IEEE 754 binary16: 1 sign bit, 5 exponent bits (bias 15), 10 mantissa bits
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+
+    match exponent {
+        // subnormal: no implicit leading 1
+        0 => sign * mantissa * f64::powi(2.0, -24),
+        0x1f => {
+            if mantissa == 0.0 {
+                sign * f64::INFINITY
+            } else {
+                f64::NAN
+            }
+        }
+        _ => sign * (1.0 + mantissa / 1024.0) * f64::powi(2.0, exponent - 15),
+    }
+}
+
+// ---- BYTE ORDER ----
+// assemble up to 8 bytes into the low bits of a u64, most significant byte first
+fn assemble_bytes(chunk: &[u8], endian: &Endianness) -> u64 {
+    match endian {
+        Endianness::Big => chunk
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+        Endianness::Little => chunk
+            .iter()
+            .rev()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+    }
+}
+
+// ---- NIBBLE STREAMS ----
+// two 4-bit codes per byte, decoded to 16-bit samples
+fn decode_nibbles(data: &[u8], order: NibbleOrder, mut decode: impl FnMut(u8) -> i16) -> Vec<f64> {
+    let full_scale = f64::powi(2.0, 15);
+    let mut formatted_data: Vec<f64> = Vec::with_capacity(data.len() * 2);
+
+    for byte in data {
+        // >> 4 selects the highest 4 bits; & 0b1111 selects the lowest 4
+        let nibbles = match order {
+            NibbleOrder::HighFirst => [byte >> 4, byte & 0b1111],
+            NibbleOrder::LowFirst => [byte & 0b1111, byte >> 4],
+        };
+        for nibble in nibbles {
+            formatted_data.push(decode(nibble) as f64 / full_scale);
+        }
+    }
+
+    formatted_data
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn planes(data: &[u8], options: &[&str]) -> Vec<Vec<f64>> {
+        let args = Args::parse_from([&["data2audio", "--layout", "planar"], options].concat());
+        let render = &args.renders()[0];
+        decode_planes(data, args.channels as usize, &[], &args, render)
+    }
+
+    #[test]
+    fn planes_start_on_sample_boundaries() {
+        // int16 ramp 0..2000 plus 3 stray bytes: 2001 bytes per plane would split a sample
+        let mut data: Vec<u8> = (0..2000i16).flat_map(i16::to_le_bytes).collect();
+        data.extend([0xff; 3]);
+
+        let channels = planes(&data, &["-f", "int16", "-c", "2"]);
+        assert_eq!(channels.len(), 2);
+        for (n, channel) in channels.iter().enumerate() {
+            let expected: Vec<f64> = (0..1000).map(|i| (n * 1000 + i) as f64 / 32768.0).collect();
+            assert_eq!(*channel, expected, "channel {}", n + 1);
+        }
+
+        // 12-bit samples: planes of whole 3-byte pairs, 10 / 3 -> 3 bytes each
+        let channels = planes(&[0xab; 10], &["-f", "packed-int", "-c", "3"]);
+        assert!(channels.iter().all(|channel| channel.len() == 2));
+    }
+
+    #[test]
+    fn short_files_still_give_every_channel() {
+        for options in [
+            &["-f", "int8", "-c", "4"][..],
+            &["-f", "int16", "-c", "2"],
+            &["-f", "ima-adpcm", "-c", "2"],
+        ] {
+            let channels = planes(&[0; 3], options);
+            assert_eq!(channels.len(), options[3].parse::<usize>().unwrap());
+            assert!(channels.iter().all(Vec::is_empty), "{options:?}");
+        }
+    }
+}
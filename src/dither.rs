@@ -0,0 +1,83 @@
+use crate::cli::Dither;
+
+// ---- NOISE SHAPING ----
+// 5-tap error-feedback filter (Wannamaker's E-weighting, designed for 44.1 kHz);
+// moves requantization noise away from ~4 kHz, where hearing is most sensitive,
+// and up towards Nyquist
+const SHAPING_COEFFS: [f64; 5] = [2.033, -2.165, 1.959, -1.590, 0.6149];
+
+// ---- RNG ----
+// xorshift64*; small and seedable so renders are reproducible
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // splitmix64 step, so nearby seeds (and 0, which xorshift can't use) start far apart
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        Rng {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniform in [-0.5, 0.5), i.e. +/- half an LSB
+    fn next_uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / f64::powi(2.0, 53) - 0.5
+    }
+}
+
+// ---- DITHERER ----
+pub struct Ditherer {
+    dither: Dither,
+    rng: Rng,
+    // most recent total requantization error first, for noise shaping
+    error_history: [f64; SHAPING_COEFFS.len()],
+}
+
+impl Ditherer {
+    pub fn new(dither: Dither, seed: u64) -> Ditherer {
+        Ditherer {
+            dither,
+            rng: Rng::new(seed),
+            error_history: [0.0; SHAPING_COEFFS.len()],
+        }
+    }
+
+    // round a sample already scaled to integer full scale (i.e. in LSBs)
+    pub fn quantize(&mut self, scaled: f64) -> f64 {
+        match self.dither {
+            Dither::None => scaled.round(),
+            Dither::Rect => (scaled + self.rng.next_uniform()).round(),
+            // sum of two uniform values: triangular PDF over +/- 1 LSB
+            Dither::Tpdf => (scaled + self.rng.next_uniform() + self.rng.next_uniform()).round(),
+            Dither::Shaped => {
+                // subtract filtered past error, so the error spectrum follows SHAPING_COEFFS
+                let feedback: f64 = SHAPING_COEFFS
+                    .iter()
+                    .zip(self.error_history.iter())
+                    .map(|(coeff, error)| coeff * error)
+                    .sum();
+                let target = scaled - feedback;
+                let quantized =
+                    (target + self.rng.next_uniform() + self.rng.next_uniform()).round();
+
+                self.error_history.rotate_right(1);
+                self.error_history[0] = quantized - target;
+
+                quantized
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+// ---- OUTPUT LEVELS ----
+// largest positive sample (relative to full scale) that fits at this bit depth
+pub fn max_level(bits: u16) -> f64 {
+    let full_scale = f64::powi(2.0, bits as i32 - 1);
+    (full_scale - 1.0) / full_scale
+}
+
+// ---- CLIPPING DETECTION ----
+// number of samples (across all channels) that will saturate when quantized to this bit depth
+pub fn count_clipped(channels: &[Vec<f64>], bits: u16) -> usize {
+    let full_scale = f64::powi(2.0, bits as i32 - 1);
+
+    channels
+        .iter()
+        .flatten()
+        .filter(|sample| {
+            let scaled = (**sample * full_scale).round();
+            scaled > full_scale - 1.0 || scaled < -full_scale
+        })
+        .count()
+}
+
+// ---- AUTOMATIC HEADROOM ----
+// turn down (never up) just enough that nothing clips at this bit depth; one gain for all channels
+pub fn auto_headroom(channels: &mut [Vec<f64>], bits: u16) {
+    let peak = channels
+        .iter()
+        .flatten()
+        .fold(0.0, |max: f64, sample| f64::max(max, sample.abs()));
+    let ceiling = max_level(bits);
+
+    if peak <= ceiling {
+        return;
+    }
+
+    let gain_lin = ceiling / peak;
+
+    for sample in channels.iter_mut().flatten() {
+        *sample *= gain_lin;
+    }
+}
+
+// ---- SOFT CLIPPING ----
+// tanh curve; unity gain for small signals, approaches the ceiling asymptotically
+pub fn soft_clip_tanh(channels: &mut [Vec<f64>], bits: u16) {
+    let ceiling = max_level(bits);
+
+    for sample in channels.iter_mut().flatten() {
+        *sample = ceiling * f64::tanh(*sample / ceiling);
+    }
+}
+
+// cubic curve x - (4/27)x^3; unity gain at 0, flattens out exactly at 1.5x the ceiling
+pub fn soft_clip_cubic(channels: &mut [Vec<f64>], bits: u16) {
+    let ceiling = max_level(bits);
+
+    for sample in channels.iter_mut().flatten() {
+        let x = f64::clamp(*sample / ceiling, -1.5, 1.5);
+        *sample = ceiling * (x - (4.0 / 27.0) * x * x * x);
+    }
+}
+
+// ---- LOOK-AHEAD LIMITER ----
+// brickwall: no sample leaves above ceiling_db (or the bit depth's max level, if lower);
+// gain reduction ramps in over attack_ms before each peak and recovers over release_ms;
+// channels share one gain, driven by whichever is loudest at each frame
+pub fn limit(
+    channels: &mut [Vec<f64>],
+    sample_rate: u32,
+    attack_ms: f64,
+    release_ms: f64,
+    ceiling_db: f64,
+    bits: u16,
+) {
+    let ceiling = f64::min(f64::powf(10.0, ceiling_db / 20.0), max_level(bits));
+    let attack_len = usize::max(
+        (attack_ms * sample_rate as f64 / 1000.0).round() as usize,
+        1,
+    );
+    let release_coeff = if release_ms > 0.0 {
+        f64::exp(-1000.0 / (release_ms * sample_rate as f64))
+    } else {
+        0.0
+    };
+
+    let len = channels
+        .iter()
+        .map(|channel| channel.len())
+        .min()
+        .unwrap_or(0);
+
+    // gain each frame would need on its own
+    let required: Vec<f64> = (0..len)
+        .map(|n| {
+            let peak = channels
+                .iter()
+                .fold(0.0, |max: f64, channel| f64::max(max, channel[n].abs()));
+            if peak > ceiling { ceiling / peak } else { 1.0 }
+        })
+        .collect();
+
+    // look ahead: lowest required gain over the next attack_len samples (sliding minimum)
+    let mut ahead = vec![1.0; required.len()];
+    let mut window: VecDeque<usize> = VecDeque::new();
+    for n in (0..required.len()).rev() {
+        while let Some(&back) = window.back()
+            && required[back] >= required[n]
+        {
+            window.pop_back();
+        }
+        window.push_back(n);
+        while let Some(&front) = window.front()
+            && front > n + attack_len
+        {
+            window.pop_front();
+        }
+        ahead[n] = required[window[0]];
+    }
+
+    // attack: averaging the look-ahead minimum over the same span turns each
+    // drop into a ramp that has fully arrived by the time the peak does
+    // window is ahead[n - attack_len..=n]; positions before the start count as 1.0
+    let mut running_sum = (attack_len + 1) as f64;
+    let mut gain = 1.0;
+
+    for n in 0..len {
+        running_sum += ahead[n];
+        running_sum -= if n > attack_len {
+            ahead[n - attack_len - 1]
+        } else {
+            1.0
+        };
+        let attack_gain = running_sum / (attack_len + 1) as f64;
+
+        // release: follow drops immediately (already smoothed), recover exponentially
+        gain = if attack_gain < gain {
+            attack_gain
+        } else {
+            attack_gain + release_coeff * (gain - attack_gain)
+        };
+
+        // guards against rounding in the running sum
+        let frame_gain = f64::min(gain, required[n]);
+        for channel in channels.iter_mut() {
+            channel[n] *= frame_gain;
+        }
+    }
+}
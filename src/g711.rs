@@ -0,0 +1,73 @@
+// ITU-T G.711 companding: each byte is a sign bit, a 3-bit segment (exponent) and a
+// 4-bit step within the segment (mantissa), expanded here to 16-bit linear PCM
+
+// mu-law bias; added before the shift so segment 0 still has a slope
+const MULAW_BIAS: i32 = 0x84;
+
+pub fn mulaw_decode(byte: u8) -> i16 {
+    // bits are stored inverted
+    let byte = !byte;
+
+    let sign = byte & 0x80;
+    let exponent = (byte >> 4) & 0b0111;
+    let mantissa = (byte & 0b1111) as i32;
+
+    let magnitude = (((mantissa << 3) + MULAW_BIAS) << exponent) - MULAW_BIAS;
+
+    if sign != 0 {
+        -magnitude as i16
+    } else {
+        magnitude as i16
+    }
+}
+
+pub fn alaw_decode(byte: u8) -> i16 {
+    // even bits are stored inverted
+    let byte = byte ^ 0x55;
+
+    let sign = byte & 0x80;
+    let exponent = (byte >> 4) & 0b0111;
+    let mantissa = (byte & 0b1111) as i32;
+
+    // + 8: middle of the step; segments above 0 also get the implicit leading bit (0x100)
+    let mut magnitude = (mantissa << 4) + 8;
+    if exponent != 0 {
+        magnitude = (magnitude + 0x100) << (exponent - 1);
+    }
+
+    // unlike mu-law, a set sign bit means positive
+    if sign != 0 {
+        magnitude as i16
+    } else {
+        -magnitude as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mulaw_known_values() {
+        assert_eq!(mulaw_decode(0xFF), 0);
+        assert_eq!(mulaw_decode(0x7F), 0);
+        assert_eq!(mulaw_decode(0x00), -32124);
+        assert_eq!(mulaw_decode(0x80), 32124);
+    }
+
+    #[test]
+    fn alaw_known_values() {
+        assert_eq!(alaw_decode(0xD5), 8);
+        assert_eq!(alaw_decode(0x55), -8);
+        assert_eq!(alaw_decode(0x2A), -32256);
+        assert_eq!(alaw_decode(0xAA), 32256);
+    }
+
+    #[test]
+    fn every_byte_is_symmetric_about_its_sign_bit() {
+        for byte in 0..0x80u8 {
+            assert_eq!(mulaw_decode(byte), -mulaw_decode(byte | 0x80));
+            assert_eq!(alaw_decode(byte), -alaw_decode(byte | 0x80));
+        }
+    }
+}
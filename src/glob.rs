@@ -0,0 +1,153 @@
+// ---- GLOB PATTERNS ----
+// "*" matches within one file or folder name, "**" any number of folders, "?" any one
+// character and "[abc]"/"[a-z]"/"[!0-9]" a set; patterns without a "/" match the
+// file or folder name at any depth, others the whole '/'-separated path
+pub fn matches(pattern: &str, path: &str) -> bool {
+    if !pattern.contains('/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return match_name(&chars(pattern), &chars(name));
+    }
+
+    // anchored to the top either way
+    let pattern: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_components(&pattern, &path)
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(name, path)| {
+            match_name(&chars(first), &chars(name)) && match_components(rest, path)
+        }),
+    }
+}
+
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some((&c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            match match_set(rest, c) {
+                Some((found, after)) => found && match_name(after, name_rest),
+                // no closing ']': just a '['
+                None => c == '[' && match_name(rest, name_rest),
+            }
+        }
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
+
+// pattern starts just after a '['; whether c is in the set, and the pattern after its ']'
+// (a ']' straight after the '[' or '[!' is part of the set)
+fn match_set(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    let start = negated as usize;
+    let mut found = false;
+
+    let mut i = start;
+    while i < pattern.len() {
+        if pattern[i] == ']' && i > start {
+            return Some((found != negated, &pattern[i + 1..]));
+        }
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            found |= (pattern[i]..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= pattern[i] == c;
+            i += 1;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_star_matches_any_number_of_folders() {
+        assert!(matches("a/**/c.wav", "a/c.wav"));
+        assert!(matches("a/**/c.wav", "a/b/c.wav"));
+        assert!(matches("a/**/c.wav", "a/b/d/c.wav"));
+        assert!(!matches("a/**/c.wav", "b/a/c.wav"));
+        assert!(matches("**/*.wav", "x.wav"));
+        assert!(matches("**/*.wav", "x/y/z.wav"));
+    }
+
+    #[test]
+    fn star_stays_within_one_name() {
+        assert!(matches("src/*.rs", "src/a.rs"));
+        assert!(!matches("src/*.rs", "src/a/b.rs"));
+        assert!(matches("*.rs", "src/a/b.rs"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(!matches("a?c", "a/c"));
+    }
+
+    #[test]
+    fn name_only_patterns_match_at_any_depth() {
+        for path in [
+            "node_modules",
+            "a/node_modules",
+            "a/b/node_modules",
+            "a/node_modules/b.js",
+        ] {
+            let name_matches = path.ends_with("node_modules");
+            assert_eq!(matches("node_modules", path), name_matches, "{path}");
+        }
+        assert!(!matches("node_modules", "node_modules_old"));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_anchored() {
+        assert!(matches("a/b", "a/b"));
+        assert!(matches("/a/b", "a/b"));
+        assert!(!matches("a/b", "x/a/b"));
+        assert!(!matches("a/b", "a/b/c"));
+        assert!(matches("a/*", "a/b"));
+        assert!(!matches("a/*", "a/b/c"));
+    }
+
+    #[test]
+    fn sets_ranges_and_negation() {
+        assert!(matches("[abc].wav", "b.wav"));
+        assert!(!matches("[abc].wav", "d.wav"));
+        assert!(matches("take[0-9]", "take7"));
+        assert!(!matches("take[0-9]", "takex"));
+        for negated in ["take[!0-9]", "take[^0-9]"] {
+            assert!(matches(negated, "takex"), "{negated}");
+            assert!(!matches(negated, "take7"), "{negated}");
+            assert!(!matches(negated, "take"), "{negated}");
+        }
+        // a '-' at the end is literal
+        assert!(matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn leading_close_bracket_is_part_of_the_set() {
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[]a]", "a"));
+        assert!(!matches("[]a]", "b"));
+        assert!(matches("[!]a]", "b"));
+        assert!(!matches("[!]a]", "]"));
+    }
+
+    #[test]
+    fn unclosed_bracket_is_literal() {
+        assert!(matches("a[b", "a[b"));
+        assert!(!matches("a[b", "ab"));
+        assert!(matches("[", "["));
+        assert!(matches("*[", "x["));
+    }
+}
@@ -0,0 +1,175 @@
+use std::f64::consts::PI;
+
+use crate::biquad::Biquad;
+
+// ---- ITU-R BS.1770 CONSTANTS ----
+// gating block length and hop, in seconds (400 ms blocks, 75% overlap)
+const BLOCK_SECONDS: f64 = 0.4;
+const HOP_SECONDS: f64 = 0.1;
+// blocks quieter than this never count towards integrated loudness
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+// second gate, relative to the loudness of the blocks that passed the first
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+// true-peak estimate: 4x oversampling with a windowed-sinc interpolator
+const TRUE_PEAK_OVERSAMPLE: usize = 4;
+const TRUE_PEAK_HALF_TAPS: isize = 12;
+
+// ---- K-WEIGHTING ----
+// high shelf (head effects) followed by the RLB high-pass;
+// coefficients re-derived for any sample rate, not just the 48 kHz tables in the spec
+fn k_weighting_filters(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    // stage 1: +4 dB shelf above ~1.7 kHz
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+
+    let k = f64::tan(PI * f0 / fs);
+    let vh = f64::powf(10.0, gain_db / 20.0);
+    let vb = f64::powf(vh, 0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    let mut shelf = Biquad::new();
+    shelf.set_coeffs(
+        (vh + vb * k / q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    // stage 2: second-order high-pass at ~38 Hz
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+
+    let k = f64::tan(PI * f0 / fs);
+    let a0 = 1.0 + k / q + k * k;
+
+    let mut high_pass = Biquad::new();
+    high_pass.set_coeffs(
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    [shelf, high_pass]
+}
+
+// mean square to LUFS, including the spec's -0.691 dB offset
+fn mean_square_to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * f64::log10(mean_square)
+}
+
+// ---- INTEGRATED LOUDNESS ----
+// gated loudness of the whole file in LUFS; None if every block is gated out (e.g. silence).
+// channels are summed with equal weight (the spec's surround weighting is not applied)
+pub fn integrated_loudness(channels: &[Vec<f64>], sample_rate: u32) -> Option<f64> {
+    let weighted: Vec<Vec<f64>> = channels
+        .iter()
+        .map(|channel| {
+            let [mut shelf, mut high_pass] = k_weighting_filters(sample_rate);
+            channel
+                .iter()
+                .map(|sample| high_pass.process_sample(shelf.process_sample(*sample)))
+                .collect()
+        })
+        .collect();
+    let len = weighted
+        .iter()
+        .map(|channel| channel.len())
+        .min()
+        .unwrap_or(0);
+
+    // files shorter than one block are measured as a single block
+    let block_len = usize::min((BLOCK_SECONDS * sample_rate as f64).round() as usize, len);
+    let hop_len = usize::max((HOP_SECONDS * sample_rate as f64).round() as usize, 1);
+
+    if block_len == 0 {
+        return None;
+    }
+
+    // mean square of every overlapping block, summed over channels
+    let block_powers: Vec<f64> = (0..=(len - block_len))
+        .step_by(hop_len)
+        .map(|start| {
+            weighted
+                .iter()
+                .map(|channel| {
+                    channel[start..start + block_len]
+                        .iter()
+                        .map(|sample| sample * sample)
+                        .sum::<f64>()
+                        / block_len as f64
+                })
+                .sum()
+        })
+        .collect();
+
+    let gated_mean = |threshold_lufs: f64| -> Option<f64> {
+        let passed: Vec<f64> = block_powers
+            .iter()
+            .copied()
+            .filter(|power| *power > 0.0 && mean_square_to_lufs(*power) > threshold_lufs)
+            .collect();
+
+        if passed.is_empty() {
+            None
+        } else {
+            Some(passed.iter().sum::<f64>() / passed.len() as f64)
+        }
+    };
+
+    let absolute_gated = gated_mean(ABSOLUTE_GATE_LUFS)?;
+    let relative_threshold = mean_square_to_lufs(absolute_gated) + RELATIVE_GATE_LU;
+
+    gated_mean(relative_threshold).map(mean_square_to_lufs)
+}
+
+// ---- TRUE PEAK ----
+// linear peak of the loudest channel, including inter-sample overs, estimated by oversampling
+pub fn true_peak(channels: &[Vec<f64>]) -> f64 {
+    channels.iter().fold(0.0, |max: f64, channel| {
+        f64::max(max, channel_true_peak(channel))
+    })
+}
+
+fn channel_true_peak(samples: &[f64]) -> f64 {
+    // one interpolation kernel per fractional position between samples
+    let kernels: Vec<Vec<f64>> = (1..TRUE_PEAK_OVERSAMPLE)
+        .map(|phase| {
+            let frac = phase as f64 / TRUE_PEAK_OVERSAMPLE as f64;
+            (-TRUE_PEAK_HALF_TAPS + 1..=TRUE_PEAK_HALF_TAPS)
+                .map(|k| {
+                    let distance = frac - k as f64;
+                    let sinc = f64::sin(PI * distance) / (PI * distance);
+                    // Hann window over the kernel's span
+                    let window = 0.5 * (1.0 + f64::cos(PI * distance / TRUE_PEAK_HALF_TAPS as f64));
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect();
+
+    let len = samples.len() as isize;
+    let mut peak = 0.0;
+
+    for n in 0..len {
+        peak = f64::max(peak, samples[n as usize].abs());
+
+        for kernel in &kernels {
+            let interpolated: f64 = kernel
+                .iter()
+                .zip(-TRUE_PEAK_HALF_TAPS + 1..=TRUE_PEAK_HALF_TAPS)
+                .filter(|(_, k)| n + k >= 0 && n + k < len)
+                .map(|(coeff, k)| coeff * samples[(n + k) as usize])
+                .sum();
+            peak = f64::max(peak, interpolated.abs());
+        }
+    }
+
+    peak
+}
@@ -3,12 +3,23 @@ use clap::Parser;
 
 // imports from submodules
 use crate::cli::Args;
-use crate::convert::convert_dir;
+use crate::convert::{convert_dir, encode_dir};
 
 // modules
+pub mod adpcm;
 pub mod biquad;
+pub mod bitstream;
 pub mod cli;
 pub mod convert;
+pub mod decode;
+pub mod dither;
+pub mod dynamics;
+pub mod g711;
+pub mod glob;
+pub mod loudness;
+pub mod normalize;
+pub mod pdm;
+pub mod template;
 pub mod vox;
 pub mod wav;
 
@@ -17,5 +28,9 @@ fn main() {
     let args = Args::parse();
 
     // handles all processing
-    convert_dir(&args);
+    if args.to_vox {
+        encode_dir(&args);
+    } else {
+        convert_dir(&args);
+    }
 }
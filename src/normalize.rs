@@ -0,0 +1,73 @@
+use crate::dynamics;
+use crate::loudness;
+
+// ---- PEAK NORMALIZATION ----
+// scale so the largest absolute sample sits at target_dbfs, where 0 dBFS is the largest
+// positive sample at this bit depth, so normalizing to 0 never clips;
+// one gain for all channels, so their balance is kept
+pub fn peak_normalize(channels: &mut [Vec<f64>], target_dbfs: f64, bits: u16) {
+    let peak = channels
+        .iter()
+        .flatten()
+        .fold(0.0, |max: f64, sample| f64::max(max, sample.abs()));
+
+    // silent (or empty) file: nothing to scale
+    if peak <= 0.0 {
+        return;
+    }
+
+    let gain_lin = f64::powf(10.0, target_dbfs / 20.0) * dynamics::max_level(bits) / peak;
+
+    apply_gain(channels, gain_lin);
+}
+
+// ---- LOUDNESS NORMALIZATION ----
+// scale to target_lufs integrated loudness; if that would push the true peak
+// above true_peak_ceiling (dBTP), back off so the peak sits at the ceiling instead
+pub fn loudness_normalize(
+    channels: &mut [Vec<f64>],
+    sample_rate: u32,
+    target_lufs: f64,
+    true_peak_ceiling: Option<f64>,
+) {
+    // silent (or too short) file: nothing to measure
+    let Some(loudness) = loudness::integrated_loudness(channels, sample_rate) else {
+        return;
+    };
+
+    let mut gain_db = target_lufs - loudness;
+
+    if let Some(ceiling_db) = true_peak_ceiling {
+        let peak = loudness::true_peak(channels);
+        if peak > 0.0 {
+            // gain applies equally to peak, so compare in dB
+            let peak_db = 20.0 * f64::log10(peak);
+            gain_db = f64::min(gain_db, ceiling_db - peak_db);
+        }
+    }
+
+    apply_gain(channels, f64::powf(10.0, gain_db / 20.0));
+}
+
+fn apply_gain(channels: &mut [Vec<f64>], gain_lin: f64) {
+    for sample in channels.iter_mut().flatten() {
+        *sample *= gain_lin;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_to_0_dbfs_reaches_but_never_clips_the_bit_depth() {
+        for bits in [8, 16, 24] {
+            let mut channels = vec![vec![0.1, -0.3, 0.25], vec![0.2, 0.05, -0.1]];
+            peak_normalize(&mut channels, 0.0, bits);
+
+            assert_eq!(channels[1][0] / channels[0][0], 2.0);
+            assert_eq!(-channels[0][1], dynamics::max_level(bits));
+            assert_eq!(dynamics::count_clipped(&channels, bits), 0);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::biquad::{AudioFilterParameters, FilterAlgorithm, FilterChain};
+
+// CIC stages; each adds ~13 dB more alias rejection at the cost of passband droop
+const CIC_ORDER: usize = 4;
+// Q per biquad for an 8th-order Butterworth low-pass (maximally flat when cascaded)
+const BUTTERWORTH_8_Q: [f64; 4] = [0.5098, 0.6013, 0.9000, 2.5629];
+// low-pass cutoff as a fraction of the output sample rate, just under its Nyquist
+const CUTOFF_RATIO: f64 = 0.45;
+
+// ---- PDM DECIMATION ----
+// 1-bit pulse-density stream (1 = +full scale, 0 = -full scale) running at
+// decimation * sample_rate, down to sample_rate:
+// CIC decimator by decimation / 2, then a biquad low-pass cascade and a final 2x decimation
+pub fn decimate(bits: &[u32], decimation: usize, sample_rate: u32) -> Vec<f64> {
+    let cic_factor = decimation / 2;
+    // CIC gain is cic_factor^order
+    let cic_gain = f64::powi(cic_factor as f64, CIC_ORDER as i32);
+
+    // ---- CIC ----
+    // integrators at the input rate, combs at the decimated rate; integer arithmetic
+    // wraps around harmlessly, since the combs undo any overflow of the integrators
+    let mut integrators = [0i64; CIC_ORDER];
+    let mut comb_delays = [0i64; CIC_ORDER];
+    let mut intermediate: Vec<f64> = Vec::with_capacity(bits.len() / cic_factor + 1);
+
+    for (n, bit) in bits.iter().enumerate() {
+        let mut value: i64 = if *bit != 0 { 1 } else { -1 };
+        for integrator in &mut integrators {
+            *integrator = integrator.wrapping_add(value);
+            value = *integrator;
+        }
+
+        if (n + 1).is_multiple_of(cic_factor) {
+            for delay in &mut comb_delays {
+                let current = value;
+                value = value.wrapping_sub(*delay);
+                *delay = current;
+            }
+            intermediate.push(value as f64 / cic_gain);
+        }
+    }
+
+    // ---- LOW-PASS AND FINAL DECIMATION ----
+    // runs at 2x the output rate, removing everything above the output's Nyquist
+    let filter_params: Vec<AudioFilterParameters> = BUTTERWORTH_8_Q
+        .iter()
+        .map(|q| {
+            AudioFilterParameters::new(
+                FilterAlgorithm::Lpf2,
+                CUTOFF_RATIO * sample_rate as f64,
+                *q,
+                0.0,
+            )
+        })
+        .collect();
+    let mut filter = FilterChain::new(&filter_params, 2 * sample_rate);
+
+    intermediate
+        .iter()
+        .map(|sample| filter.process_sample(*sample))
+        .step_by(2)
+        .collect()
+}
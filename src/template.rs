@@ -0,0 +1,172 @@
+// ---- OUTPUT NAME TEMPLATES ----
+// e.g. "{relpath}_{format}_{rate}" - text is copied as-is, {placeholders} are filled in
+pub const PLACEHOLDERS: [&str; 9] = [
+    "stem", "ext", "relpath", "format", "endian", "rate", "offset", "hash8", "index",
+];
+
+pub enum Piece<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+// split into literal text and placeholder names, checking every placeholder is known
+pub fn parse(template: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = vec![];
+    let mut rest = template;
+
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err(format!("unmatched '}}' in name template '{template}'"));
+        }
+        let Some(close) = rest[open..].find('}') else {
+            return Err(format!("unmatched '{{' in name template '{template}'"));
+        };
+
+        let name = &rest[open + 1..open + close];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder '{{{name}}}'; expected one of {{{}}}",
+                PLACEHOLDERS.join("}, {")
+            ));
+        }
+
+        if open > 0 {
+            pieces.push(Piece::Text(&rest[..open]));
+        }
+        pieces.push(Piece::Placeholder(name));
+        rest = &rest[open + close + 1..];
+    }
+
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+
+    Ok(pieces)
+}
+
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    parse(template).is_ok_and(|pieces| {
+        pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Placeholder(name) if *name == placeholder))
+    })
+}
+
+// fill in each placeholder with value(name); template must already have passed parse()
+pub fn render(template: &str, mut value: impl FnMut(&str) -> String) -> String {
+    let mut rendered = String::new();
+
+    for piece in parse(template).unwrap_or_default() {
+        match piece {
+            Piece::Text(text) => rendered.push_str(text),
+            Piece::Placeholder(name) => rendered.push_str(&value(name)),
+        }
+    }
+
+    rendered
+}
+
+// ---- CONTENT HASH ----
+// 32-bit FNV-1a, for {hash8}; fed in pieces, so large regions needn't be in memory at once
+pub struct Fnv1a(u32);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a::new()
+    }
+}
+
+impl Fnv1a {
+    pub fn new() -> Fnv1a {
+        Fnv1a(0x811c9dc5)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 ^= *byte as u32;
+            self.0 = self.0.wrapping_mul(0x01000193);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(template: &str) -> String {
+        render(template, |placeholder| placeholder.to_uppercase())
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_and_text_kept() {
+        assert_eq!(fill("{stem}"), "STEM");
+        assert_eq!(
+            fill("{relpath}_{format}-{rate}Hz.x"),
+            "RELPATH_FORMAT-RATEHz.x"
+        );
+        assert_eq!(fill("out/{index}{hash8}"), "out/INDEXHASH8");
+        assert_eq!(fill("plain"), "plain");
+        assert_eq!(fill(""), "");
+    }
+
+    #[test]
+    fn unmatched_braces_are_rejected() {
+        for template in [
+            "{stem",
+            "stem}",
+            "{stem}}",
+            "{{stem}",
+            "a{",
+            "}a",
+            "{stem}_{rate",
+        ] {
+            assert!(parse(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        for template in ["{}", "{name}", "{Stem}", "{ stem }", "{stem}_{hash}"] {
+            let error = parse(template).err().unwrap_or_default();
+            assert!(
+                error.starts_with("unknown placeholder"),
+                "{template}: {error}"
+            );
+        }
+
+        for placeholder in PLACEHOLDERS {
+            assert!(parse(&format!("{{{placeholder}}}")).is_ok());
+        }
+    }
+
+    #[test]
+    fn uses_finds_only_whole_placeholders() {
+        assert!(uses("{stem}_{hash8}", "hash8"));
+        assert!(!uses("{stem}_hash8", "hash8"));
+        assert!(!uses("{stem}", "ste"));
+        // invalid templates use nothing
+        assert!(!uses("{hash8}_{", "hash8"));
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        let hash = |data: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.update(data);
+            hash.finish()
+        };
+        assert_eq!(hash(b""), 0x811c9dc5);
+        assert_eq!(hash(b"a"), 0xe40c292c);
+        assert_eq!(hash(b"foobar"), 0xbf9cf968);
+
+        // fed in pieces or all at once
+        let mut pieces = Fnv1a::new();
+        pieces.update(b"foo");
+        pieces.update(b"bar");
+        assert_eq!(pieces.finish(), hash(b"foobar"));
+    }
+}
@@ -16,9 +16,28 @@ impl VoxState {
             step_index: 0,
         }
     }
-    // pub fn vox_encode(&mut self, input: &i16) -> u8 {
+    pub fn vox_encode(&mut self, input: &i16) -> u8 {
+        let step_size = VOX_STEP_TABLE[self.step_index as usize];
+        // scale from 16-bit to 12-bit, then compare with what the decoder has so far
+        let mut diff = (input >> 4) - self.predictor;
+
+        // sign is 4th bit
+        let mut out_nibble: u8 = 0;
+        if diff < 0 {
+            out_nibble |= 0b1000;
+            diff *= -1;
+        }
+
+        // magnitude: how many quarter steps fit in diff (max 7); the decoder rebuilds
+        // (2 * magnitude + 1) * ss(n) / 8, the middle of that quarter step
+        let magnitude = i16::min((4 * diff) / step_size, 0b0111);
+        out_nibble |= magnitude as u8;
 
-    // }
+        // run the decoder so predictor/step index track what playback will produce
+        self.vox_decode(&out_nibble);
+
+        out_nibble
+    }
     pub fn vox_decode(&mut self, in_nibble: &u8) -> i16 {
         // get step size from last time's index before updating
         let step_size = VOX_STEP_TABLE[self.step_index as usize];
@@ -50,12 +69,299 @@ impl VoxState {
         self.predictor * 16
     }
 }
+
+// ---- YAMAHA ADPCM-A ----
+// YM2610 (Neo Geo) sample ROMs: same step table as VOX, but bigger index jumps, and
+// the 12-bit accumulator wraps around instead of clamping
+pub struct AdpcmAState {
+    accumulator: i16,
+    step_index: i16,
+}
+
+impl Default for AdpcmAState {
+    fn default() -> Self {
+        AdpcmAState::new()
+    }
+}
+
+impl AdpcmAState {
+    pub fn new() -> AdpcmAState {
+        AdpcmAState {
+            accumulator: 0,
+            step_index: 0,
+        }
+    }
+
+    pub fn adpcm_a_decode(&mut self, in_nibble: &u8) -> i16 {
+        let step_size = VOX_STEP_TABLE[self.step_index as usize];
+
+        let magnitude = in_nibble & 0b0111;
+        let mut delta = ((2 * (magnitude as i16) + 1) * step_size) >> 3;
+        if in_nibble & 0b1000 != 0 {
+            delta *= -1;
+        }
+
+        // keep 12 bits, then shift up and back down to sign-extend bit 11
+        let wrapped = (self.accumulator + delta) & 0x0fff;
+        self.accumulator = (wrapped << 4) >> 4;
+
+        self.step_index = i16::clamp(
+            self.step_index + ADPCM_A_INDEX_TABLE[magnitude as usize],
+            0,
+            (VOX_STEP_TABLE.len() as i16) - 1,
+        );
+
+        // scale from 12-bit to 16-bit
+        self.accumulator * 16
+    }
+}
+
+// ---- YAMAHA ADPCM-B ----
+// YM2610/YM2608/Y8950 "DELTA-T": no step table; the step size is scaled by a
+// multiplier per code, and the accumulator is a full 16 bits
+pub struct AdpcmBState {
+    accumulator: i32,
+    step_size: i32,
+}
+
+impl Default for AdpcmBState {
+    fn default() -> Self {
+        AdpcmBState::new()
+    }
+}
+
+impl AdpcmBState {
+    pub fn new() -> AdpcmBState {
+        AdpcmBState {
+            accumulator: 0,
+            step_size: ADPCM_B_STEP_MIN,
+        }
+    }
+
+    pub fn adpcm_b_decode(&mut self, in_nibble: &u8) -> i16 {
+        let magnitude = (in_nibble & 0b0111) as i32;
+
+        let mut delta = ((2 * magnitude + 1) * self.step_size) / 8;
+        if in_nibble & 0b1000 != 0 {
+            delta *= -1;
+        }
+
+        self.accumulator = i32::clamp(self.accumulator + delta, i16::MIN as i32, i16::MAX as i32);
+        // multipliers are in 64ths
+        self.step_size = i32::clamp(
+            (self.step_size * ADPCM_B_STEP_SCALE[magnitude as usize]) / 64,
+            ADPCM_B_STEP_MIN,
+            ADPCM_B_STEP_MAX,
+        );
+
+        self.accumulator as i16
+    }
+}
+
+// ---- OKI MSM6258 ----
+// X68000 and arcade boards: the original OKI hardware form of the VOX algorithm, which
+// sums step, step/2, step/4 and step/8 separately (each truncated) rather than scaling once
+pub struct Msm6258State {
+    signal: i16,
+    step_index: i16,
+}
+
+impl Default for Msm6258State {
+    fn default() -> Self {
+        Msm6258State::new()
+    }
+}
+
+impl Msm6258State {
+    pub fn new() -> Msm6258State {
+        Msm6258State {
+            signal: 0,
+            step_index: 0,
+        }
+    }
+
+    pub fn msm6258_decode(&mut self, in_nibble: &u8) -> i16 {
+        let step_size = VOX_STEP_TABLE[self.step_index as usize];
+
+        // ss(n)/8 always, plus ss(n), ss(n)/2, ss(n)/4 for each magnitude bit set
+        let mut delta = step_size / 8;
+        if in_nibble & 0b0100 != 0 {
+            delta += step_size;
+        }
+        if in_nibble & 0b0010 != 0 {
+            delta += step_size / 2;
+        }
+        if in_nibble & 0b0001 != 0 {
+            delta += step_size / 4;
+        }
+        if in_nibble & 0b1000 != 0 {
+            delta *= -1;
+        }
+
+        // clamp between 12-bit signed min/max value, like VOX
+        self.signal = i16::clamp(self.signal + delta, -i16::pow(2, 11), i16::pow(2, 11) - 1);
+        self.step_index = i16::clamp(
+            self.step_index + ADPCM_INDEX_TABLE[*in_nibble as usize],
+            0,
+            (VOX_STEP_TABLE.len() as i16) - 1,
+        );
+
+        // scale from 12-bit to 16-bit
+        self.signal * 16
+    }
+}
 // duplicate values from spec; can index w/ whole nibble, incl sign bit (4th)
 // increment up/down thru this table...
-const ADPCM_INDEX_TABLE: [i16; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+pub const ADPCM_INDEX_TABLE: [i16; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
 // ...use (clamped) index table to index this array for step size
 const VOX_STEP_TABLE: [i16; 49] = [
     16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130,
     143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796,
     876, 963, 1060, 1166, 1282, 1411, 1552,
 ];
+// ADPCM-A index adjustment, by magnitude (3 LSBs) only
+const ADPCM_A_INDEX_TABLE: [i16; 8] = [-1, -1, -1, -1, 2, 5, 7, 9];
+// ADPCM-B step size multipliers (in 64ths), by magnitude, and step size limits
+const ADPCM_B_STEP_SCALE: [i32; 8] = [57, 57, 57, 57, 77, 102, 128, 153];
+const ADPCM_B_STEP_MIN: i32 = 127;
+const ADPCM_B_STEP_MAX: i32 = 24576;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_then_decoding_a_sine_keeps_its_shape() {
+        // 440 Hz at 8 kHz, half scale
+        let input: Vec<i16> = (0..8000)
+            .map(|n| {
+                (16384.0 * f64::sin(2.0 * std::f64::consts::PI * 440.0 * n as f64 / 8000.0)) as i16
+            })
+            .collect();
+
+        let mut encoder = VoxState::new();
+        let mut decoder = VoxState::new();
+        let output: Vec<i16> = input
+            .iter()
+            .map(|sample| decoder.vox_decode(&encoder.vox_encode(sample)))
+            .collect();
+
+        // skip the first 100 ms, while the step size adapts up from its minimum
+        let (signal, noise) =
+            input[800..]
+                .iter()
+                .zip(&output[800..])
+                .fold((0.0, 0.0), |(signal, noise), (x, y)| {
+                    let error = (*x - *y) as f64;
+                    (signal + (*x as f64).powi(2), noise + error * error)
+                });
+        let snr_db = 10.0 * f64::log10(signal / noise);
+        assert!(snr_db > 25.0, "SNR {snr_db} dB");
+    }
+
+    #[test]
+    fn re_encoding_decoded_output_gives_back_the_same_nibbles() {
+        // random magnitudes, but always stepping back towards 0 and keeping the step
+        // size moderate, so the predictor never reaches the 12-bit limits
+        let mut seed: u32 = 12345;
+        let mut decoder = VoxState::new();
+        let mut nibbles = vec![];
+        let mut decoded = vec![];
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let mask = if decoder.step_index < 40 {
+                0b0111
+            } else {
+                0b0011
+            };
+            let sign = if decoder.predictor > 0 { 0b1000 } else { 0 };
+            let nibble = ((seed >> 24) as u8 & mask) | sign;
+
+            nibbles.push(nibble);
+            decoded.push(decoder.vox_decode(&nibble));
+        }
+        assert!(
+            decoded
+                .iter()
+                .all(|sample| (-2048 * 16 + 1..2047 * 16).contains(sample)),
+            "test stream clamps"
+        );
+
+        let mut encoder = VoxState::new();
+        let encoded: Vec<u8> = decoded.iter().map(|s| encoder.vox_encode(s)).collect();
+        assert_eq!(encoded, nibbles);
+    }
+
+    #[test]
+    fn encoding_full_scale_swings_does_not_overflow() {
+        let mut encoder = VoxState::new();
+        let mut decoder = VoxState::new();
+        let mut decoded = vec![];
+
+        for n in 0..200 {
+            let input = if n % 2 == 0 { i16::MAX } else { i16::MIN };
+            let nibble = encoder.vox_encode(&input);
+            // always the largest step towards the input
+            assert_eq!(nibble, if n % 2 == 0 { 0x7 } else { 0xF });
+            decoded.push(decoder.vox_decode(&nibble));
+        }
+        assert_eq!(encoder.predictor, decoder.predictor);
+
+        // once the step size tops out, each sample moves by (15 * 1552) >> 3
+        for pair in decoded[190..].windows(2) {
+            assert_eq!((pair[1] as i32 - pair[0] as i32).abs(), 2910 * 16);
+        }
+    }
+
+    #[test]
+    fn adpcm_a_accumulator_wraps_instead_of_clamping() {
+        // step 16, magnitude 4: delta (9 * 16) >> 3 = 18, taking 2040 to 2058 = -2038 in 12 bits
+        let mut state = AdpcmAState {
+            accumulator: 2040,
+            step_index: 0,
+        };
+        assert_eq!(state.adpcm_a_decode(&0x4), -2038 * 16);
+
+        let mut state = AdpcmAState {
+            accumulator: -2040,
+            step_index: 0,
+        };
+        assert_eq!(state.adpcm_a_decode(&0xC), 2038 * 16);
+
+        // where VOX clamps
+        let mut state = VoxState {
+            predictor: 2040,
+            step_index: 0,
+        };
+        assert_eq!(state.vox_decode(&0x4), 2047 * 16);
+    }
+
+    #[test]
+    fn adpcm_b_scales_its_step_size() {
+        // step 127: 15 * 127 / 8, then step 127 * 153 / 64 = 303 and -(15 * 303 / 8)
+        let mut state = AdpcmBState::new();
+        assert_eq!(state.adpcm_b_decode(&0x7), 238);
+        assert_eq!(state.adpcm_b_decode(&0xF), 238 - 568);
+
+        // step stays at its minimum: 127 / 8 each time
+        let mut state = AdpcmBState::new();
+        assert_eq!(state.adpcm_b_decode(&0x0), 15);
+        assert_eq!(state.adpcm_b_decode(&0x0), 30);
+    }
+
+    #[test]
+    fn msm6258_truncates_each_step_term_separately() {
+        // step 19: 2 + 19 + 9 + 4, where VOX's (15 * 19) >> 3 gives 35
+        let mut state = Msm6258State {
+            signal: 0,
+            step_index: 2,
+        };
+        assert_eq!(state.msm6258_decode(&0x7), 34 * 16);
+
+        let mut state = VoxState {
+            predictor: 0,
+            step_index: 2,
+        };
+        assert_eq!(state.vox_decode(&0x7), 35 * 16);
+    }
+}
@@ -1,12 +1,11 @@
-// use std::any::TypeId;
-// use std::ops::{ShlAssign, ShrAssign};
-use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use hound::{self, Sample, WavReader, WavSpec, WavWriter};
-// use i24::I24;
 
-use crate::cli::{Args, SampleFormat};
+use crate::cli::{Args, OutFormat, Render};
+use crate::dither::Ditherer;
 
 pub fn read_file_as_wav<T>(path: &Path) -> Result<(Vec<T>, WavSpec), hound::Error>
 where
@@ -21,67 +20,199 @@ where
     // return
     Ok((input, spec))
 }
-// &[i16] instead of &Vec<i16> - https://rust-lang.github.io/rust-clippy/master/index.html#ptr_arg
-pub fn write_file_as_wav<T>(data: &[T], path: &PathBuf, args: &Args) -> Result<(), hound::Error>
-where
-    T: Copy + Sample + 'static, //+ ShlAssign + ShlAssign<i32> + ShrAssign + ShrAssign<i32>  // &TypeId::of<T>() requires 'static
-{
-    let fmt_num_bits = HashMap::<SampleFormat, u16>::from([
-        (SampleFormat::Int8, 8),
-        (SampleFormat::Int16, 16),
-        (SampleFormat::Int24, 24),
-        (SampleFormat::Int32, 32),
-        (SampleFormat::Vox, 16),
-    ]);
-
-    // let out_bits: u16;
-    // let out_format: SampleFormat;
-    //
-    // if let Some(format) = &args.out_format {
-    //     out_bits = fmt_num_bits[format];
-    //     out_format = *format;
-    // } else {
-    //     out_bits = fmt_num_bits[&args.format];
-    //     out_format = args.format;
-    // }
+
+// any int or float WAV, mixed down to mono 16-bit
+pub fn read_file_as_mono_i16(path: &Path) -> Result<(Vec<i16>, WavSpec), hound::Error> {
+    // scale to [-1.0, 1.0) first, whatever the bit depth
+    let (samples, spec): (Vec<f64>, WavSpec) = match read_file_as_wav::<i32>(path) {
+        Ok((samples, spec)) => {
+            let full_scale = f64::powi(2.0, spec.bits_per_sample as i32 - 1);
+            let scaled = samples.iter().map(|s| *s as f64 / full_scale).collect();
+            (scaled, spec)
+        }
+        // float WAVs can't be read as integers
+        Err(hound::Error::InvalidSampleFormat) => {
+            let (samples, spec) = read_file_as_wav::<f32>(path)?;
+            (samples.iter().map(|s| *s as f64).collect(), spec)
+        }
+        Err(e) => return Err(e),
+    };
+
+    // average each frame's channels
+    let mono = samples
+        .chunks(spec.channels as usize)
+        .map(|frame| {
+            let mean = frame.iter().sum::<f64>() / frame.len() as f64;
+            f64::clamp((mean * 32768.0).round(), -32768.0, 32767.0) as i16
+        })
+        .collect();
+
+    Ok((mono, spec))
+}
+
+// &[Vec<f64>] instead of &Vec<Vec<f64>> - https://rust-lang.github.io/rust-clippy/master/index.html#ptr_arg
+// one Vec per channel, of equal length; samples are in [-1.0, 1.0), as returned by decoding,
+// and quantized here to the output bit depth, so e.g. int32 input written as 16-bit keeps its top 16 bits
+pub fn write_file_as_wav(
+    channels: &[Vec<f64>],
+    path: &PathBuf,
+    args: &Args,
+    render: &Render,
+) -> Result<(), hound::Error> {
+    let out_format = args.output_format(render);
+    let bits = out_format.bits();
+    let num_channels = channels.len() as u16;
+
+    // WAV stores frames: one sample per channel, side by side
+    let frames = channels.first().map_or(0, |channel| channel.len());
+    let data: Vec<f64> = (0..frames)
+        .flat_map(|n| channels.iter().map(move |channel| channel[n]))
+        .collect();
+
+    // hound only writes 32-bit float
+    if out_format == OutFormat::Float64 {
+        return write_f64_wav(&data, path, num_channels, render.samplerate);
+    }
 
     let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: args.samplerate,
-        // bits_per_sample: out_bits,
-        bits_per_sample: fmt_num_bits[&args.format],
-        sample_format: hound::SampleFormat::Int,
+        channels: num_channels,
+        sample_rate: render.samplerate,
+        bits_per_sample: bits,
+        sample_format: if out_format.is_float() {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
     };
 
+    // hound's byte counters are u32 and overflow rather than erroring past 4 GiB, so check
+    // first; 60 covers its largest (WAVE_FORMAT_EXTENSIBLE) header
+    riff_len(data.len() as u64 * (bits / 8) as u64, 60)?;
+
     //writer
     let mut writer = WavWriter::create(path, spec)?;
 
-    // let shift_amount = (fmt_num_bits[&args.format] as i32) - (fmt_num_bits[&out_format] as i32);
-    // if shift_amount != 0 {
-    //     if shift_amount > 0 {
-    //         for sample in data {
-    //             let mut out_sample = *sample;
-    //             out_sample <<= shift_amount;
-    //             match out_format {
-    //                 SampleFormat::Int8 => {
-    //                     writer.write_sample(*sample as i8)?;
-    //                 }
-    //             }
-    //         }
-    //     }
-    // } else if shift_amount < 0 {
-    //     for sample in data {
-    //         let mut out_sample = *sample;
-    //         out_sample >>= shift_amount.abs();
-    //         writer.write_sample(*sample)?;
-    //     }
-    // } else {
-    for sample in data {
-        writer.write_sample(*sample)?;
+    if out_format.is_float() {
+        for sample in &data {
+            writer.write_sample(*sample as f32)?;
+        }
+        writer.finalize()?;
+        return Ok(());
+    }
+
+    // separate dither state per channel; offset seeds so channels get uncorrelated noise
+    let mut ditherers: Vec<Ditherer> = (0..num_channels)
+        .map(|channel| Ditherer::new(args.dither, args.seed.wrapping_add(channel as u64)))
+        .collect();
+
+    for (i, sample) in data.iter().enumerate() {
+        let ditherer = &mut ditherers[i % num_channels as usize];
+        let quantized = quantize(*sample, bits, ditherer);
+        // hound picks the on-disk width from the Rust type, so match it to bits
+        match bits {
+            8 => writer.write_sample(quantized as i8)?,
+            16 => writer.write_sample(quantized as i16)?,
+            _ => writer.write_sample(quantized)?,
+        }
     }
-    // }
 
     writer.finalize()?;
 
     Ok(())
 }
+
+// ---- QUANTIZE ----
+// scale to integer full scale and round (with dither, if any); saturates at the format's min/max
+fn quantize(sample: f64, bits: u16, ditherer: &mut Ditherer) -> i32 {
+    let full_scale = f64::powi(2.0, bits as i32 - 1);
+    f64::clamp(
+        ditherer.quantize(sample * full_scale),
+        -full_scale,
+        full_scale - 1.0,
+    ) as i32
+}
+
+// ---- 64-BIT FLOAT WAV ----
+// WAVE_FORMAT_IEEE_FLOAT, written by hand since hound only supports 32-bit float
+fn write_f64_wav(
+    data: &[f64],
+    path: &PathBuf,
+    channels: u16,
+    sample_rate: u32,
+) -> Result<(), hound::Error> {
+    const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+    const BYTES_PER_SAMPLE: u16 = 8;
+
+    // sizes that don't fit the header's fields are refused before creating the file
+    let block_align = channels
+        .checked_mul(BYTES_PER_SAMPLE)
+        .ok_or(hound::Error::TooWide)?;
+    let byte_rate = sample_rate
+        .checked_mul(block_align as u32)
+        .ok_or(hound::Error::TooWide)?;
+    let num_frames =
+        u32::try_from(data.len() / channels as usize).map_err(|_| hound::Error::TooWide)?;
+    let data_len = data.len() as u64 * BYTES_PER_SAMPLE as u64;
+    // "WAVE" + fmt chunk (8 + 18) + fact chunk (8 + 4) + data chunk header (8)
+    let riff_len = riff_len(data_len, 4 + 26 + 12 + 8)?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_len.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    // non-PCM formats use the 18-byte fmt chunk, with a zero-length extension
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&18u32.to_le_bytes())?;
+    writer.write_all(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+
+    // ...and need a fact chunk with the number of sample frames
+    writer.write_all(b"fact")?;
+    writer.write_all(&4u32.to_le_bytes())?;
+    writer.write_all(&num_frames.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&(data_len as u32).to_le_bytes())?;
+    for sample in data {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+// the RIFF chunk size for data_len bytes of samples after header_len bytes of header, or
+// hound's TooWide if it doesn't fit the u32 size fields (i.e. the file would be over 4 GiB)
+fn riff_len(data_len: u64, header_len: u32) -> Result<u32, hound::Error> {
+    u32::try_from(data_len)
+        .ok()
+        .and_then(|data_len| data_len.checked_add(header_len))
+        .ok_or(hound::Error::TooWide)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_headers_are_refused() {
+        assert_eq!(riff_len(100, 50).ok(), Some(150));
+        assert_eq!(riff_len(u32::MAX as u64 - 50, 50).ok(), Some(u32::MAX));
+        assert!(riff_len(u32::MAX as u64 - 49, 50).is_err());
+        assert!(riff_len(u32::MAX as u64 + 1, 0).is_err());
+
+        // 9000 * 8-byte samples don't fit a u16 block align; refused before creating the file
+        let path = std::env::temp_dir().join("data2audio-too-wide.wav");
+        let result = write_f64_wav(&[0.0; 9000], &path, 9000, 44100);
+        assert!(matches!(result, Err(hound::Error::TooWide)));
+        assert!(!path.exists());
+    }
+}